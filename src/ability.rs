@@ -0,0 +1,228 @@
+use crate::board::Board;
+use crate::board::Noise;
+use crate::enemy::Enemy;
+use crate::math::Direction;
+use crate::state::MapObject;
+use crate::state::State;
+use abes_nice_things::{FromBinary, ToBinary};
+use anyhow::Result;
+
+/// Something the player can do by spending energy, anything aimed uses the selector
+#[derive(Clone, Copy, Debug)]
+pub struct Ability {
+    pub name: &'static str,
+    /// The energy it takes
+    pub cost: usize,
+    /// Does the ability and returns if it went through, the energy is only spent if it did
+    activate: fn(&mut State) -> bool,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum AbilityID {
+    Dash = 0,
+    Shove = 1,
+    Bolt = 2,
+}
+impl ToBinary for AbilityID {
+    fn to_binary(&self, binary: &mut dyn std::io::prelude::Write) -> Result<()> {
+        self.to_raw().to_binary(binary)
+    }
+}
+impl FromBinary for AbilityID {
+    fn from_binary(binary: &mut dyn std::io::prelude::Read) -> Result<Self> {
+        let raw = u8::from_binary(binary)?;
+        if raw >= ABILITIES.len() as u8 {
+            anyhow::bail!("Invalid ability id {raw}");
+        }
+        Ok(AbilityID::from_raw(raw))
+    }
+}
+impl AbilityID {
+    pub fn from_raw(raw: u8) -> AbilityID {
+        if raw >= ABILITIES.len() as u8 {
+            panic!("Tried to make invalid AbilityID: ({raw})")
+        }
+        unsafe { std::mem::transmute(raw) }
+    }
+    fn to_raw(self) -> u8 {
+        unsafe { std::mem::transmute(self) }
+    }
+    pub fn get(self) -> &'static Ability {
+        &ABILITIES[self.to_raw() as usize]
+    }
+    pub fn iter() -> impl Iterator<Item = AbilityID> {
+        (0..ABILITIES.len()).map(|index| AbilityID::from_raw(index as u8))
+    }
+}
+/// Uses the ability if the player has the energy for it, returns if the turn should be
+/// incremented
+pub fn use_ability(state: &mut State, id: AbilityID) -> bool {
+    let ability = id.get();
+    if state.player.energy < ability.cost {
+        state.feedback = format!("Not enough energy to {}", ability.name.to_lowercase());
+        return false;
+    }
+    if !(ability.activate)(state) {
+        return false;
+    }
+    state.player.energy -= ability.cost;
+    true
+}
+/// How many turns it takes to get back one energy
+pub const ENERGY_REGEN_INTERVAL: usize = 4;
+/// How many tiles a dash can cover
+const DASH_DISTANCE: usize = 3;
+/// How many tiles a shove pushes an enemy
+const SHOVE_DISTANCE: usize = 2;
+/// The damage dealt to a shoved enemy if it gets slammed into something
+const SHOVE_DAMAGE: usize = 1;
+const BOLT_RANGE: usize = 8;
+const BOLT_DAMAGE: usize = 2;
+/// The direction the selector is in from the player, going with whichever axis is further. None if
+/// the selector is on the player
+fn direction_to_selector(state: &State) -> Option<Direction> {
+    let from = state.player.position;
+    let to = state.player.selector;
+    if from == to {
+        return None;
+    }
+    let diff = from.abs_diff(to);
+    Some(if diff.x >= diff.y {
+        if to.x > from.x {
+            Direction::Right
+        } else {
+            Direction::Left
+        }
+    } else if to.y > from.y {
+        Direction::Down
+    } else {
+        Direction::Up
+    })
+}
+pub static ABILITIES: &[Ability] = &[
+    Ability {
+        name: "Dash",
+        cost: 2,
+        // Goes as far as it can towards the selector
+        activate: |state| {
+            let Some(direction) = direction_to_selector(state) else {
+                state.feedback = "Select where to dash".to_string();
+                return false;
+            };
+            let mut moved = false;
+            for _ in 0..DASH_DISTANCE {
+                if !state
+                    .board
+                    .player_can_move(state.player.position, direction)
+                {
+                    break;
+                }
+                state.player.position += direction;
+                moved = true;
+            }
+            if !moved {
+                state.feedback = "Something is in the way".to_string();
+                return false;
+            }
+            // A hasted dash is free so no turn would update it
+            Board::update_field_of_view(state);
+            true
+        },
+    },
+    Ability {
+        name: "Shove",
+        cost: 1,
+        // Pushes the enemy at the selector away, hurting it if it hits something
+        activate: |state| {
+            let target = state.player.selector;
+            if !state.player.position.is_adjacent(target) {
+                state.feedback = "Select something next to you to shove".to_string();
+                return false;
+            }
+            let Some(id) = state.board.get_enemy_at_position(target) else {
+                state.feedback = "There is nothing to shove".to_string();
+                return false;
+            };
+            let direction = direction_to_selector(state).unwrap();
+            state.board.make_noise(state.player.position, Noise::COMBAT);
+            // It doesn't get to keep walking wherever it was going
+            state.board[id].as_mut().unwrap().move_target = None;
+            for _ in 0..SHOVE_DISTANCE {
+                let position = state.board[id].as_ref().unwrap().get_position();
+                if !Board::enemy_can_move(state, position, direction) {
+                    if (state.board[id].as_ref().unwrap().get_vtable().damage)(
+                        state,
+                        id,
+                        SHOVE_DAMAGE,
+                    ) {
+                        state.stats.kills += 1;
+                    }
+                    break;
+                }
+                Enemy::move_position(state, id, position + direction);
+            }
+            true
+        },
+    },
+    Ability {
+        name: "Bolt",
+        cost: 3,
+        // Fires at the selector, hitting the first enemy in the way
+        activate: |state| {
+            if state.player.position == state.player.selector {
+                state.feedback = "Select something to fire at".to_string();
+                return false;
+            }
+            state.board.make_noise(state.player.position, Noise::COMBAT);
+            let (hit, _) =
+                crate::raycast::RayCast::new(state.player.position, state.player.selector)
+                    .max_range(Some(BOLT_RANGE))
+                    .resolve(state);
+            if let Some(MapObject::Enemy(id)) = hit {
+                if (state.board[id].as_ref().unwrap().get_vtable().damage)(state, id, BOLT_DAMAGE) {
+                    state.stats.kills += 1;
+                }
+                state.feedback = "The bolt hit".to_string();
+            } else {
+                state.feedback = "The bolt missed".to_string();
+            }
+            true
+        },
+    },
+];
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effect::{EffectID, EffectTracker};
+    use crate::input::Input;
+    use crate::math::Vector;
+    use crate::state::Entity;
+    #[test]
+    fn abilities() {
+        let mut state = State::blank_headless();
+        state.player.energy = 2;
+        // Dashing goes towards the selector
+        state.player.selector = Vector::new(9, 1);
+        state.simulate([Input::UseAbility(AbilityID::Dash)]);
+        assert_eq!(state.player.position, Vector::new(4, 1));
+        assert_eq!(state.player.energy, 0);
+
+        // Without the energy nothing happens and no time passes
+        let turns = state.total_turns;
+        state.simulate([Input::UseAbility(AbilityID::Dash)]);
+        assert_eq!(state.player.position, Vector::new(4, 1));
+        assert_eq!(state.total_turns, turns);
+
+        // It comes back over time
+        state.simulate(std::iter::repeat_n(
+            Input::SkipTurn,
+            ENERGY_REGEN_INTERVAL * 2,
+        ));
+        assert_eq!(state.player.energy, 2);
+
+        // The menu doesn't get around being stunned
+        EffectTracker::apply(&mut state, Entity::Player, EffectID::Stun, None);
+        assert!(!state.act_from_menu(|state| use_ability(state, AbilityID::Dash)));
+        assert_eq!(state.player.energy, 2);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::board::AxisLength;
+use anyhow::{Context, Result, bail};
+
+pub const HELP: &str = "\
+Usage: main [OPTIONS]
+
+Options:
+    --seed <SEED>       The seed for everything random, a random one is picked if not given
+    --size <SIZE>       The size of the map, either small (64) or full (1024) [default: full]
+    --budget <BUDGET>   The enemy budget for map generation [default: 10000]
+    --load <PATH>       Start from a save file instead of generating a new board
+    --record <PATH>     Record everything that happens so that it can be replayed
+    --replay <PATH>     Play back a recording, then continue from where it ended
+    --replay-delay <MS> How long to wait between replayed inputs [default: 100]
+    --autosave <TURNS>  How many turns between autosaves, 0 turns it off [default: 100]
+    -h, --help          Print this message";
+
+/// Everything which can be changed from the command line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Args {
+    pub seed: Option<u64>,
+    pub axis_length: AxisLength,
+    pub budget: usize,
+    /// The path to a save file to start from, when this is given the seed, size and budget do
+    /// nothing because the board comes from the save
+    pub load: Option<String>,
+    /// Where to record to
+    pub record: Option<String>,
+    /// The recording to replay, like load this makes the seed, size and budget do nothing
+    pub replay: Option<String>,
+    /// In milliseconds
+    pub replay_delay: u64,
+    /// In turns, 0 means never
+    pub autosave: usize,
+    pub help: bool,
+}
+impl Default for Args {
+    fn default() -> Self {
+        Args {
+            seed: None,
+            axis_length: AxisLength::Full,
+            budget: crate::dungeon::Dungeon::DEFAULT_BUDGET,
+            load: None,
+            record: None,
+            replay: None,
+            replay_delay: 100,
+            autosave: 100,
+            help: false,
+        }
+    }
+}
+impl Args {
+    /// Parses the arguments the program was started with
+    pub fn parse() -> Result<Args> {
+        Args::parse_from(std::env::args().skip(1))
+    }
+    /// Parses the given arguments, which should NOT include the program name
+    pub fn parse_from(mut arguments: impl Iterator<Item = String>) -> Result<Args> {
+        let mut args = Args::default();
+        while let Some(argument) = arguments.next() {
+            match argument.as_str() {
+                "--seed" => {
+                    let value = next_value(&mut arguments, &argument)?;
+                    args.seed = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("Invalid seed: {value}"))?,
+                    );
+                }
+                "--size" => {
+                    args.axis_length = match next_value(&mut arguments, &argument)?.as_str() {
+                        "small" | "64" => AxisLength::Small,
+                        "full" | "1024" => AxisLength::Full,
+                        other => bail!("Invalid size: {other}, expected small or full"),
+                    };
+                }
+                "--budget" => {
+                    let value = next_value(&mut arguments, &argument)?;
+                    args.budget = value
+                        .parse()
+                        .with_context(|| format!("Invalid budget: {value}"))?;
+                }
+                "--load" => args.load = Some(next_value(&mut arguments, &argument)?),
+                "--record" => args.record = Some(next_value(&mut arguments, &argument)?),
+                "--replay" => args.replay = Some(next_value(&mut arguments, &argument)?),
+                "--replay-delay" => {
+                    let value = next_value(&mut arguments, &argument)?;
+                    args.replay_delay = value
+                        .parse()
+                        .with_context(|| format!("Invalid replay delay: {value}"))?;
+                }
+                "--autosave" => {
+                    let value = next_value(&mut arguments, &argument)?;
+                    args.autosave = value
+                        .parse()
+                        .with_context(|| format!("Invalid autosave interval: {value}"))?;
+                }
+                "-h" | "--help" => args.help = true,
+                other => bail!("Unknown argument: {other}"),
+            }
+        }
+        if args.load.is_some() && args.replay.is_some() {
+            bail!("Cannot both load a save and replay a recording");
+        }
+        Ok(args)
+    }
+}
+fn next_value(arguments: &mut impl Iterator<Item = String>, argument: &str) -> Result<String> {
+    arguments
+        .next()
+        .with_context(|| format!("Missing value for {argument}"))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn parse(arguments: &[&str]) -> Result<Args> {
+        Args::parse_from(arguments.iter().map(|argument| argument.to_string()))
+    }
+    #[test]
+    fn no_arguments() {
+        assert_eq!(parse(&[]).unwrap(), Args::default());
+    }
+    #[test]
+    fn all_arguments() {
+        assert_eq!(
+            parse(&[
+                "--seed",
+                "42",
+                "--size",
+                "small",
+                "--budget",
+                "7",
+                "--load",
+                "save",
+                "--record",
+                "recording",
+                "--replay-delay",
+                "5",
+                "--autosave",
+                "0"
+            ])
+            .unwrap(),
+            Args {
+                seed: Some(42),
+                axis_length: AxisLength::Small,
+                budget: 7,
+                load: Some("save".to_string()),
+                record: Some("recording".to_string()),
+                replay: None,
+                replay_delay: 5,
+                autosave: 0,
+                help: false,
+            }
+        );
+    }
+    #[test]
+    fn invalid_arguments() {
+        assert!(parse(&["--seed"]).is_err());
+        assert!(parse(&["--seed", "nope"]).is_err());
+        assert!(parse(&["--size", "medium"]).is_err());
+        assert!(parse(&["--what"]).is_err());
+        assert!(parse(&["--load", "save", "--replay", "recording"]).is_err());
+    }
+}
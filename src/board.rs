@@ -11,10 +11,12 @@ use crate::Vector;
 use crate::Zone;
 use crate::enemy::Enemy;
 use crate::math::Direction;
+use crate::screen::Screen;
 use crate::state::State;
 use abes_nice_things::MaxVec;
 use abes_nice_things::Number;
 use abes_nice_things::PrimAs;
+use abes_nice_things::Style;
 use abes_nice_things::{FromBinary, ToBinary};
 use anyhow::{Context, Result, bail};
 use std::collections::BinaryHeap;
@@ -54,6 +56,20 @@ pub struct Board {
     /// The number of turns spent on this map
     local_turns: usize,
     rooms: Vec<Room>,
+    /// Whether the player has ever seen each tile, this has the same restrictions as tiles
+    remembered: Vec<bool>,
+    /// What the player can currently see, this is recalculated every turn and on load so it is
+    /// not saved
+    seen: HashSet<Vector<usize>>,
+    /// Noises made since the enemies last listened
+    noises: Vec<Noise>,
+    /// The noises the enemies are listening to this turn, this does not get saved because it
+    /// is only used while they think
+    heard_noises: Vec<Noise>,
+    /// Where the boss is fought, if the map has one
+    boss_arena: Option<BossArena>,
+    /// Items lying on the floor, more than one can be in the same place
+    items: Vec<(Vector<usize>, crate::item::ItemID)>,
 }
 impl ToBinary for Board {
     fn to_binary(&self, binary: &mut dyn Write) -> Result<()> {
@@ -69,23 +85,82 @@ impl ToBinary for Board {
             enemy.as_ref().to_binary(binary)?;
         }
         self.local_turns.to_binary(binary)?;
-        self.rooms.to_binary(binary)
+        self.rooms.to_binary(binary)?;
+        // Packed 8 to a byte because there is one for every tile
+        for chunk in self.remembered.chunks(8) {
+            let mut bools = [false; 8];
+            bools[..chunk.len()].copy_from_slice(chunk);
+            abes_nice_things::compact(bools).to_binary(binary)?;
+        }
+        self.noises.to_binary(binary)?;
+        self.boss_arena.as_ref().to_binary(binary)?;
+        self.items.to_binary(binary)
     }
 }
 impl FromBinary for Board {
     fn from_binary(binary: &mut dyn std::io::prelude::Read) -> Result<Self> {
         let axis_length = AxisLength::from_binary(binary)?;
-        Ok(Board {
+        let mut board = Board {
             tiles: <Vec<Option<Tile>>>::from_binary(binary)?,
             room_map: <Vec<RoomIDFlagged>>::from_binary(binary)?,
             axis_length,
-            // Viewport has to be recalculated on load for reasons that should be obvious
-            viewport_size: crate::calc_desired_dimensions(crate::get_terminal_size())
+            // Viewport has to be recalculated on load for reasons that should be obvious, the
+            // state does it once it knows the screen size
+            viewport_size: crate::MINIMUM_VIEWPORT
                 .min(Vector::equal_from_axis(axis_length.to_inner())),
             enemies: <Vec<Option<Enemy>>>::from_binary(binary)?,
             local_turns: usize::from_binary(binary)?,
             rooms: <Vec<Room>>::from_binary(binary)?,
-        })
+            remembered: Vec::new(),
+            seen: HashSet::new(),
+            noises: Vec::new(),
+            heard_noises: Vec::new(),
+            boss_arena: None,
+            items: Vec::new(),
+        };
+        let length = board.tiles.len();
+        // Before version 2 nothing was remembered
+        if crate::save::loading_version() >= 2 {
+            board.remembered.reserve(length);
+            while board.remembered.len() < length {
+                board
+                    .remembered
+                    .extend(abes_nice_things::expand(u8::from_binary(binary)?));
+            }
+            board.remembered.truncate(length);
+        } else {
+            board.remembered = vec![false; length];
+        }
+        // Before version 3 there were no noises
+        if crate::save::loading_version() >= 3 {
+            board.noises = <Vec<Noise>>::from_binary(binary)?;
+        }
+        // Before version 4 there were no bosses
+        if crate::save::loading_version() >= 4 {
+            board.boss_arena = <Option<BossArena>>::from_binary(binary)?;
+        }
+        // Before version 9 there were no items
+        if crate::save::loading_version() >= 9 {
+            board.items = <Vec<(Vector<usize>, crate::item::ItemID)>>::from_binary(binary)?;
+        }
+        Ok(board)
+    }
+}
+#[cfg(test)]
+impl Board {
+    /// Writes the board how it was before saves had versions. Enemies were written differently
+    /// back then so it can't have any
+    pub fn write_version_0(&self, binary: &mut dyn Write) -> Result<()> {
+        assert!(self.enemies.is_empty());
+        self.axis_length.to_binary(binary)?;
+        self.tiles.len().to_binary(binary)?;
+        for tile in self.tiles.iter() {
+            tile.as_ref().to_binary(binary)?;
+        }
+        self.room_map.to_binary(binary)?;
+        self.enemies.len().to_binary(binary)?;
+        self.local_turns.to_binary(binary)?;
+        self.rooms.to_binary(binary)
     }
 }
 
@@ -106,6 +181,12 @@ impl Board {
             enemies: Vec::new(),
             local_turns: 0,
             rooms: Vec::new(),
+            remembered: vec![false; axis_length.to_inner() * axis_length.to_inner()],
+            seen: HashSet::new(),
+            noises: Vec::new(),
+            heard_noises: Vec::new(),
+            boss_arena: None,
+            items: Vec::new(),
         })
     }
     pub fn axis_length(&self) -> AxisLength {
@@ -120,12 +201,21 @@ impl Board {
     /// Then we pathfind
     pub fn increment(state: &mut State) {
         state.board.local_turns += 1;
+        Board::update_boss_arena(state);
+        // Anything which makes noise while the enemies think is heard next turn
+        state.board.heard_noises = std::mem::take(&mut state.board.noises);
         Board::run_thinkers(state);
         Board::pathfind(state);
+        Board::increment_enemy_effects(state);
     }
     pub fn get_viewport_size(&self) -> Vector<usize> {
         self.viewport_size
     }
+    /// The viewport will be as close to the desired size as the map allows
+    pub fn set_viewport_size(&mut self, desired_viewport: Vector<usize>) {
+        self.viewport_size =
+            desired_viewport.min(Vector::equal_from_axis(self.axis_length.to_inner()));
+    }
     pub fn get_local_turn(&self) -> usize {
         self.local_turns
     }
@@ -177,40 +267,65 @@ impl Board {
         )
         .unwrap()
     }
-    /// Zeros the cursor and draws the tiles onto the screen and clears the screen, this is the first layer of rendering.
+    /// Draws the tiles into the screen, this is the first layer of rendering.
     ///
     /// Additionally it draws the border of the viewport
-    pub fn render_tiles(&self, viewport: Zone<usize>, buffer: &mut impl Write) {
-        // Putting the cursor in the top corner
-        write!(buffer, "\x1b[H").unwrap();
-        for (position, last) in viewport.scanlines() {
+    pub fn render_tiles(&self, viewport: Zone<usize>, screen: &mut Screen) {
+        for (position, _) in viewport.scanlines() {
             if let Some(tile) = self[position] {
                 let (ch, style) = tile.render(self, position);
-                match style {
-                    Some(style) => write!(buffer, "{style}{ch}\x1b[0m").unwrap(),
-                    None => write!(buffer, "{ch}").unwrap(),
-                }
-            } else {
-                write!(buffer, " ").unwrap();
+                let style = match self.get_visibility(position) {
+                    Visibility::Seen => style,
+                    Visibility::Remembered => Some(*style.unwrap_or(Style::new()).dim(true)),
+                    Visibility::Unknown => continue,
+                };
+                screen.set(position - viewport.top_left(), ch, style);
+            }
+        }
+        // The right and bottom of the border
+        for y in 0..viewport.height() {
+            screen.set(
+                Vector::new(viewport.width(), y),
+                Board::VIEWPORT_BORDER_RIGHT,
+                None,
+            );
+        }
+        for x in 0..viewport.width() {
+            screen.set(
+                Vector::new(x, viewport.height()),
+                Board::VIEWPORT_BORDER_BOTTOM,
+                None,
+            );
+        }
+        screen.set(
+            Vector::new(viewport.width(), viewport.height()),
+            Board::VIEWPORT_BORDER_CORNER,
+            None,
+        );
+    }
+    /// Draws the items on top of the tiles, items which are only remembered are dimmed like tiles
+    pub fn render_items(&self, viewport: Zone<usize>, screen: &mut Screen) {
+        for (position, item) in self.items.iter() {
+            if !viewport.contains(*position) {
+                continue;
             }
-            if last {
-                // erase until end of line and draw right border
-                writeln!(buffer, "{}\x1b[0K", Board::VIEWPORT_BORDER_RIGHT).unwrap();
+            let mut style = *Style::new().green().intense(true);
+            match self.get_visibility(*position) {
+                Visibility::Seen => {}
+                Visibility::Remembered => {
+                    style.dim(true);
+                }
+                Visibility::Unknown => continue,
             }
+            screen.set(
+                *position - viewport.top_left(),
+                item.get().symbol,
+                Some(style),
+            );
         }
-        // erase from cursor to end of screen and draw bottom of border
-        write!(
-            buffer,
-            "{}{}\x1b[0J",
-            Board::VIEWPORT_BORDER_BOTTOM
-                .to_string()
-                .repeat(viewport.width()),
-            Board::VIEWPORT_BORDER_CORNER
-        )
-        .unwrap();
     }
-    /// Moves the cursor about to draw the enemies, this is the second layer of rendering.
-    pub fn render_enemies(state: &mut State, viewport: Zone<usize>, buffer: &mut impl Write) {
+    /// Draws the enemies on top of the items, this is the third layer of rendering.
+    pub fn render_enemies(state: &mut State, viewport: Zone<usize>, screen: &mut Screen) {
         // The weird iterator stuff ensures that we only are rendering enemies which are alive and
         // on screen on top of getting us the on screen position of that enemy
 
@@ -219,18 +334,213 @@ impl Board {
                 continue;
             }
             let enemy = state.board.enemies[index].as_ref().unwrap();
-            if !viewport.contains(enemy.get_position()) {
+            if !viewport.contains(enemy.get_position())
+                || !state.board.is_seen(enemy.get_position())
+            {
                 continue;
             }
-            let screen_position = enemy.get_position() - viewport.top_left() + 1;
+            let screen_position = enemy.get_position() - viewport.top_left();
             let (ch, style) = Enemy::render(state, EnemyID(index));
-            write!(
-                buffer,
-                "\x1b[{};{}H{style}{ch}\x1b[0m",
-                screen_position.y, screen_position.x
-            )
-            .unwrap();
+            screen.set(screen_position, ch, Some(style));
+        }
+    }
+}
+
+// ITEMS
+impl Board {
+    pub fn add_item(&mut self, position: Vector<usize>, item: crate::item::ItemID) {
+        self.items.push((position, item));
+    }
+    /// Removes the item at the position which was put there last
+    pub fn take_item_at(&mut self, position: Vector<usize>) -> Option<crate::item::ItemID> {
+        let index = self
+            .items
+            .iter()
+            .rposition(|(item_position, _)| *item_position == position)?;
+        Some(self.items.remove(index).1)
+    }
+    pub fn get_items_at(
+        &self,
+        position: Vector<usize>,
+    ) -> impl Iterator<Item = crate::item::ItemID> + '_ {
+        self.items
+            .iter()
+            .filter(move |(item_position, _)| *item_position == position)
+            .map(|(_, item)| *item)
+    }
+}
+
+// BOSSES
+/// The room the boss is fought in, its doors are locked from when the player walks in until the
+/// boss dies
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BossArena {
+    pub room: RoomID,
+    pub boss: EnemyID,
+    pub locked: bool,
+}
+impl ToBinary for BossArena {
+    fn to_binary(&self, binary: &mut dyn Write) -> Result<()> {
+        self.room.to_binary(binary)?;
+        self.boss.to_binary(binary)?;
+        self.locked.to_binary(binary)
+    }
+}
+impl FromBinary for BossArena {
+    fn from_binary(binary: &mut dyn std::io::prelude::Read) -> Result<Self> {
+        Ok(BossArena {
+            room: RoomID::from_binary(binary)?,
+            boss: EnemyID::from_binary(binary)?,
+            locked: bool::from_binary(binary)?,
+        })
+    }
+}
+impl Board {
+    pub fn get_boss_arena(&self) -> Option<BossArena> {
+        self.boss_arena
+    }
+    /// Locks the arena once the player is in it and every door could be closed, and unlocks it
+    /// once the boss is dead
+    pub fn update_boss_arena(state: &mut State) {
+        let Some(arena) = state.board.boss_arena else {
+            return;
+        };
+        let doors: Vec<Vector<usize>> = state.board[arena.room]
+            .connections
+            .iter()
+            .map(|(position, _)| *position)
+            .collect();
+        if state.board[arena.boss].is_none() {
+            if arena.locked {
+                for door in doors {
+                    if let Some(Tile::Door { open, .. }) = &mut state.board[door] {
+                        *open = true;
+                    }
+                }
+                state.board.boss_arena.as_mut().unwrap().locked = false;
+                state.feedback = "The boss is dead and the doors unseal".to_string();
+            }
+            return;
+        }
+        if !arena.locked
+            && state.board.get_room_id_of_coord(state.player.position) == Some(arena.room)
+        {
+            state.board[arena.boss].as_mut().unwrap().flags.wake();
+            let mut all_closed = true;
+            for door in doors {
+                // Doors can't be closed on top of enemies, they are tried again next turn
+                if state.board.is_enemy_at_position(door) {
+                    all_closed = false;
+                    continue;
+                }
+                if let Some(Tile::Door { open, .. }) = &mut state.board[door] {
+                    *open = false;
+                }
+            }
+            // Otherwise a door left open would still count as locked
+            if all_closed {
+                state.board.boss_arena.as_mut().unwrap().locked = true;
+                state.feedback = "The doors slam shut behind you".to_string();
+            }
+        }
+    }
+    /// Checks if the position is a door which can't be opened because of a boss fight
+    pub fn is_door_locked(&self, position: Vector<usize>) -> bool {
+        if let Some(arena) = self.boss_arena
+            && arena.locked
+            && let Some(Tile::Door { rooms, .. }) = self[position]
+        {
+            rooms.contains(&arena.room)
+        } else {
+            false
+        }
+    }
+}
+
+// VISIBILITY
+/// How much the player knows about a tile
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Visibility {
+    /// Never seen, it is not drawn at all
+    Unknown,
+    /// Seen before but not now, it is drawn dimmed and might be out of date
+    Remembered,
+    /// In sight right now
+    Seen,
+}
+impl Board {
+    /// How far the player can see in tiles
+    pub const SIGHT_RANGE: usize = 15;
+    pub fn get_visibility(&self, position: Vector<usize>) -> Visibility {
+        if self.seen.contains(&position) {
+            Visibility::Seen
+        } else if self.remembered[convert_z_order_index(position, self.axis_length).unwrap()] {
+            Visibility::Remembered
+        } else {
+            Visibility::Unknown
+        }
+    }
+    pub fn is_seen(&self, position: Vector<usize>) -> bool {
+        self.seen.contains(&position)
+    }
+    /// Recalculates what the player can see by casting rays from the player to every tile on the
+    /// edge of a square around them, anything seen is also remembered.
+    ///
+    /// This needs to be run whenever the player could see something different, which is every
+    /// turn, on load and whenever the player is moved outside of a turn
+    pub fn update_field_of_view(state: &mut State) {
+        let origin = state.player.position;
+        let range = if state
+            .player
+            .effect_tracker
+            .has(crate::effect::EffectID::Blindness)
+        {
+            crate::effect::BLIND_SIGHT_RANGE
+        } else {
+            Board::SIGHT_RANGE
+        };
+        let max = state.board.axis_length.to_inner() - 1;
+        let left = origin.x.saturating_sub(range);
+        let right = (origin.x + range).min(max);
+        let top = origin.y.saturating_sub(range);
+        let bottom = (origin.y + range).min(max);
+
+        let mut targets = Vec::new();
+        for x in left..=right {
+            targets.push(Vector::new(x, top));
+            targets.push(Vector::new(x, bottom));
+        }
+        for y in top..=bottom {
+            targets.push(Vector::new(left, y));
+            targets.push(Vector::new(right, y));
+        }
+
+        let mut seen = HashSet::new();
+        seen.insert(origin);
+        for target in targets {
+            if target == origin {
+                continue;
+            }
+            let (_, path) = crate::raycast::RayCast::new(origin, target)
+                .can_hit_enemy(false)
+                .stop_at_target(true)
+                .record_path(true)
+                .resolve(state);
+            // The rays go to the corners of a square so they are cut down to a circle
+            seen.extend(path.unwrap().into_iter().filter(|position| {
+                let offset = position.abs_diff(origin);
+                offset.x * offset.x + offset.y * offset.y <= range * range
+            }));
+        }
+        for position in seen.iter() {
+            let index = convert_z_order_index(*position, state.board.axis_length).unwrap();
+            state.board.remembered[index] = true;
         }
+        state.board.seen = seen;
+    }
+    /// Remembers every tile, this doesn't change what is currently seen
+    pub fn reveal_all(&mut self) {
+        self.remembered.fill(true);
     }
 }
 
@@ -316,6 +626,31 @@ impl Board {
 // ENEMIES
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct EnemyID(pub usize);
+/// Something the enemies can hear, it wakes up any enemy close enough to it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Noise {
+    pub position: Vector<usize>,
+    /// How far away it can be heard from, this is halved when heard from a different room
+    pub loudness: usize,
+}
+impl Noise {
+    pub const COMBAT: usize = 6;
+    pub const DOOR: usize = 4;
+}
+impl ToBinary for Noise {
+    fn to_binary(&self, binary: &mut dyn Write) -> Result<()> {
+        self.position.to_binary(binary)?;
+        self.loudness.to_binary(binary)
+    }
+}
+impl FromBinary for Noise {
+    fn from_binary(binary: &mut dyn std::io::prelude::Read) -> Result<Self> {
+        Ok(Noise {
+            position: <Vector<usize>>::from_binary(binary)?,
+            loudness: usize::from_binary(binary)?,
+        })
+    }
+}
 impl ToBinary for EnemyID {
     fn to_binary(&self, binary: &mut dyn Write) -> Result<()> {
         self.0.to_binary(binary)
@@ -330,6 +665,14 @@ impl FromBinary for EnemyID {
     }
 }
 impl Board {
+    /// Makes a noise which the enemies will hear the next time they think
+    pub fn make_noise(&mut self, position: Vector<usize>, loudness: usize) {
+        self.noises.push(Noise { position, loudness });
+    }
+    /// The noises the enemies can hear this turn
+    pub fn get_heard_noises(&self) -> &[Noise] {
+        &self.heard_noises
+    }
     pub fn add_enemy(&mut self, enemy: crate::enemy::Enemy) -> EnemyID {
         self.enemies.push(Some(enemy));
         Enemy::inital_room_memoize(self, EnemyID(self.enemies.len() - 1));
@@ -346,12 +689,40 @@ impl Board {
     /// This requires mutable accesss to all enemies
     pub fn run_thinkers(state: &mut State) {
         for index in 0..state.board.enemies.len() {
-            if state.board.enemies[index].is_some() {
-                let vtable = state.board.enemies[index].as_ref().unwrap().get_vtable();
+            if state.board.enemies[index].is_none() {
+                continue;
+            }
+            for _ in 0..Enemy::actions_this_turn(state, EnemyID(index)) {
+                // It could have died during its last action
+                let Some(enemy) = state.board.enemies[index].as_ref() else {
+                    break;
+                };
+                let vtable = enemy.get_vtable();
                 (vtable.think)(state, EnemyID(index));
             }
         }
     }
+    /// Runs the effects on every enemy then counts them down and runs the on_ends of those that
+    /// finished
+    pub fn increment_enemy_effects(state: &mut State) {
+        for index in 0..state.board.enemies.len() {
+            if state.board.enemies[index].is_some() {
+                crate::effect::EffectTracker::run_on_turns(
+                    state,
+                    crate::state::Entity::Enemy(EnemyID(index)),
+                );
+            }
+            // The effects could have killed it
+            if let Some(enemy) = state.board.enemies[index].as_mut() {
+                let finished = enemy.effect_tracker.decriment();
+                crate::effect::EffectTracker::run_on_ends(
+                    state,
+                    crate::state::Entity::Enemy(EnemyID(index)),
+                    finished,
+                );
+            }
+        }
+    }
     /// This requires mutable access to all enemies
     pub fn pathfind(state: &mut State) {
         Board::inter_room_pathfind(state);
@@ -362,7 +733,9 @@ impl Board {
             if let Some(enemy) = state.board.enemies[index].as_ref()
                 && enemy.flags.should_path()
             {
-                Enemy::intra_room_pathfind(state, EnemyID(index));
+                for _ in 0..Enemy::actions_this_turn(state, EnemyID(index)) {
+                    Enemy::intra_room_pathfind(state, EnemyID(index));
+                }
             }
         }
     }
@@ -750,3 +1123,42 @@ fn validate_tile_indexing() {
         }
     }
 }
+#[cfg(test)]
+#[test]
+fn field_of_view() {
+    use crate::input::Input;
+    use std::collections::VecDeque;
+    let mut state = State::blank_headless();
+    for y in 0..AxisLength::Small.to_inner() {
+        state.board[Vector::new(5, y)] = Some(Tile::Wall);
+    }
+    Board::update_field_of_view(&mut state);
+    assert_eq!(
+        state.board.get_visibility(Vector::new(3, 3)),
+        Visibility::Seen
+    );
+    assert_eq!(
+        state.board.get_visibility(Vector::new(5, 1)),
+        Visibility::Seen
+    );
+    assert_eq!(
+        state.board.get_visibility(Vector::new(7, 1)),
+        Visibility::Unknown
+    );
+
+    state.simulate([Input::Walk(Direction::Down); 20]);
+    assert_eq!(
+        state.board.get_visibility(Vector::new(3, 3)),
+        Visibility::Remembered
+    );
+
+    // What is remembered is saved but what is seen is not
+    let mut buf = VecDeque::new();
+    state.board.to_binary(&mut buf).unwrap();
+    let board = Board::from_binary(&mut buf).unwrap();
+    assert_eq!(
+        board.get_visibility(Vector::new(1, 21)),
+        Visibility::Remembered
+    );
+    assert_eq!(board.get_visibility(Vector::new(7, 1)), Visibility::Unknown);
+}
@@ -20,6 +20,9 @@ use anyhow::Result;
 //
 // adjacent rooms get doors connecting them at the midpoint in the shared
 // section of wall
+//
+// The biggest room in the bottom right quadrant, furthest from spawn, becomes the boss arena. It
+// only gets the boss, none of its budget is spent.
 
 // Because all of this is happening in a different thread, we do not need to care about
 // performance*
@@ -28,6 +31,15 @@ pub fn generate(
     axis_length: AxisLength,
     desired_viewport: Vector<usize>,
     budget: usize,
+) -> Result<Board> {
+    generate_with_boss(axis_length, desired_viewport, budget, true)
+}
+/// Tests turn the boss off to reach the fallbacks for when no room can be the arena
+fn generate_with_boss(
+    axis_length: AxisLength,
+    desired_viewport: Vector<usize>,
+    budget: usize,
+    boss: bool,
 ) -> Result<Board> {
     let mut rooms = Vec::new();
     rooms.push(Room {
@@ -54,7 +66,12 @@ pub fn generate(
     Room::set_room_map(&mut board);
     let spawn_budget = Room::remove_budget_of_spawn(&mut rooms, 0);
     Room::reallocate_spawn_budget(&mut rooms, 0, spawn_budget);
+    if boss {
+        Room::place_boss(&mut board);
+    }
+    Room::place_stairs(&mut board);
     Room::place_enemies(&mut board, &rooms, 0);
+    Room::place_items(&mut board);
     validate(&board);
     Ok(board)
 }
@@ -70,6 +87,8 @@ struct Room {
 impl Room {
     const MINIMUM_AXIS: usize = 12; // 3 increments of 4
     const MINIMUM_STOP_DEPTH: usize = 3;
+    /// One in this many rooms gets an item
+    const ITEM_CHANCE: u8 = 4;
     fn subdivide(rooms: &mut Vec<Room>, index: usize, depth: usize, max_early_stop: f64) {
         let smallest_axis_length = rooms[index]
             .bounds
@@ -358,6 +377,72 @@ impl Room {
         // We have hit the lotto winner
         rooms[index].budget += budget;
     }
+    /// Picks the boss arena and puts the boss in the middle of it
+    fn place_boss(board: &mut Board) {
+        let half = board.axis_length.to_inner() / 2;
+        let far_quadrant = (0..board.rooms.len())
+            .filter(|index| {
+                let bounds = board.rooms[*index].get_bounds();
+                bounds.left() >= half && bounds.top() >= half
+            })
+            .max_by_key(|index| board.rooms[*index].get_bounds().area());
+        // If no room fits in the quadrant then use whatever room is in the far corner
+        let corner = board.axis_length.to_inner() - 2;
+        let Some(room) = far_quadrant
+            .map(super::room::room_id)
+            .or_else(|| board.get_room_id_of_coord(Vector::new(corner, corner)))
+        else {
+            return;
+        };
+        let bounds = board[room].get_bounds();
+        // The boss shouldn't be waiting at spawn
+        if bounds.contains(Vector::new(1, 1)) {
+            return;
+        }
+        let position = Vector::new(
+            bounds.left().midpoint(bounds.right()),
+            bounds.top().midpoint(bounds.bottom()),
+        );
+        let boss = board.add_enemy(Enemy::new(crate::enemy::VTableID::Boss, position));
+        board.boss_arena = Some(super::BossArena {
+            room,
+            boss,
+            locked: false,
+        });
+    }
+    /// Puts the stairs in the bottom right of the boss arena so that the boss has to be beaten
+    /// first, if there is no arena then they go in whichever room reaches furthest into the far
+    /// corner
+    fn place_stairs(board: &mut Board) {
+        let Some(room) = board.boss_arena.map(|arena| arena.room).or_else(|| {
+            (0..board.rooms.len())
+                .max_by_key(|index| board.rooms[*index].get_bounds().bottom_right().sum_axes())
+                .map(super::room::room_id)
+        }) else {
+            return;
+        };
+        let bounds = board[room].get_bounds();
+        board[Vector::new(bounds.right() - 1, bounds.bottom() - 1)] = Some(super::Tile::Stairs);
+    }
+    /// Scatters items around, at most one per room
+    fn place_items(board: &mut Board) {
+        for index in 0..board.rooms.len() {
+            if u8::random() % Room::ITEM_CHANCE != 0 {
+                continue;
+            }
+            let Some(interior) = board.rooms[index].get_bounds().shrink_by(1) else {
+                continue;
+            };
+            let position = interior.generate();
+            // Not on top of the stairs
+            if board[position].is_some() {
+                continue;
+            }
+            let item =
+                crate::item::ItemID::from_raw((usize::random() % crate::item::ITEMS.len()) as u8);
+            board.add_item(position, item);
+        }
+    }
     fn place_enemies(board: &mut Board, rooms: &Vec<Room>, index: usize) {
         // 7,931,287th verse, same as the first
         if let Some(children) = rooms[index].children {
@@ -365,6 +450,12 @@ impl Room {
             Room::place_enemies(board, rooms, children[1]);
             return;
         }
+        // The boss arena only has the boss
+        if let Some(arena) = board.boss_arena
+            && board[arena.room].get_bounds() == rooms[index].bounds
+        {
+            return;
+        }
         let mut budget = rooms[index].budget;
         // Have to account for the walls
         let room_bounds = rooms[index].bounds.shrink_by(1).unwrap();
@@ -377,7 +468,8 @@ impl Room {
             // We will attempt 10 times per center
             for _ in 0..10 {
                 let position = room_bounds.generate();
-                if board.is_enemy_at_position(position) {
+                // Not on top of another enemy or the stairs
+                if board.is_enemy_at_position(position) || board[position].is_some() {
                     continue;
                 }
                 if let Some(vtable) = Enemy::pick_vtable_from_budget(&mut budget, None) {
@@ -391,6 +483,10 @@ impl Room {
             }
         }
 
+        // There is nothing to spawn around
+        if centers.is_empty() {
+            return;
+        }
         // Now that we have our centers we need to go until we run out of budget and place things
         // near them in a round robin in a 10 radius square clamped to the edge of the room
         while budget > 0 {
@@ -411,7 +507,7 @@ impl Room {
                 // usual 10 attempts max
                 for _ in 0..10 {
                     let position = spawn_bounds.generate();
-                    if board.is_enemy_at_position(position) {
+                    if board.is_enemy_at_position(position) || board[position].is_some() {
                         continue;
                     }
                     if let Some(vtable) =
@@ -516,6 +612,13 @@ fn validate(board: &Board) {
             );
         }
     }
+    // There has to be a way down
+    assert!(
+        (0..board.axis_length.to_inner())
+            .flat_map(|y| (0..board.axis_length.to_inner()).map(move |x| Vector::new(x, y)))
+            .any(|position| board[position] == Some(Tile::Stairs)),
+        "There were no stairs"
+    );
     // Ensure there are no overlapping enemies and all enemies are on empty tiles
     for (first_index, first_enemy) in board.enemies.iter().enumerate() {
         let first_enemy = first_enemy.as_ref().unwrap();
@@ -529,3 +632,14 @@ fn validate(board: &Board) {
         }
     }
 }
+#[cfg(test)]
+#[test]
+fn stairs_without_boss() {
+    // Without an arena the stairs go in a room which also gets enemies
+    for seed in 0..100 {
+        crate::random::seed(seed);
+        let board =
+            generate_with_boss(AxisLength::Small, Vector::new(80, 24), 10000, false).unwrap();
+        assert!(board.boss_arena.is_none());
+    }
+}
@@ -14,27 +14,34 @@ pub enum Tile {
     Wall,
     /// Like a wall, but you can make it pretend it doesn't exist. For a while anyway.
     Door { open: bool, rooms: [RoomID; 2] },
+    /// The way down to the next floor, it can be walked over like the floor
+    Stairs,
 }
+// Before version 8 the discriminant was a bool, walls and doors keep 0 and 1 from it so those
+// saves read the same
 impl ToBinary for Tile {
     fn to_binary(&self, binary: &mut dyn std::io::prelude::Write) -> Result<()> {
         match self {
-            Tile::Wall => false.to_binary(binary),
+            Tile::Wall => 0_u8.to_binary(binary),
             Tile::Door { open, rooms } => {
-                true.to_binary(binary)?;
+                1_u8.to_binary(binary)?;
                 open.to_binary(binary)?;
                 rooms.to_binary(binary)
             }
+            Tile::Stairs => 2_u8.to_binary(binary),
         }
     }
 }
 impl FromBinary for Tile {
     fn from_binary(binary: &mut dyn std::io::prelude::Read) -> Result<Self> {
-        Ok(match bool::from_binary(binary)? {
-            false => Tile::Wall,
-            true => Tile::Door {
+        Ok(match u8::from_binary(binary)? {
+            0 => Tile::Wall,
+            1 => Tile::Door {
                 open: bool::from_binary(binary)?,
                 rooms: <[RoomID; 2]>::from_binary(binary)?,
             },
+            2 => Tile::Stairs,
+            other => anyhow::bail!("Invalid tile discriminant {other}"),
         })
     }
 }
@@ -47,6 +54,7 @@ impl Tile {
                 (get_wall_char(board, position), Some(CLOSED_DOOR_STYLE))
             }
             Tile::Door { open: true, .. } => OPEN_DOOR,
+            Tile::Stairs => STAIRS,
         }
     }
     /// Returns if the player will collide with this tile (not be able to walk through it)
@@ -54,13 +62,15 @@ impl Tile {
         match self {
             Tile::Wall => true,
             Tile::Door { open, .. } => !open,
+            Tile::Stairs => false,
         }
     }
     pub fn is_enemy_collidable(&self) -> bool {
         self.is_player_collidable() // for now these are aligned
     }
     pub fn is_wall_connectable(&self) -> bool {
-        true // both walls and doors are always connectable
+        // both walls and doors are always connectable
+        !matches!(self, Tile::Stairs)
     }
     pub fn is_raycast_hittable(&self) -> bool {
         self.is_player_collidable() //for now these are aligned
@@ -68,6 +78,7 @@ impl Tile {
 }
 const OPEN_DOOR: (char, Option<Style>) = (WALL_ALL_SIDES, Some(*Style::new().green()));
 const CLOSED_DOOR_STYLE: Style = *Style::new().red();
+const STAIRS: (char, Option<Style>) = ('>', Some(*Style::new().yellow()));
 const WALL_ALL_SIDES: char = '╬';
 const WALL_T_DOWN: char = '╦';
 const WALL_T_UP: char = '╩';
@@ -161,6 +172,7 @@ mod tests {
                 open: true,
                 rooms: [room_id(777), room_id(1234)],
             },
+            Tile::Stairs,
         ];
         let mut buf = VecDeque::new();
         for test in tests.into_iter() {
@@ -169,4 +181,20 @@ mod tests {
         }
         assert_eq!(buf.len(), 0)
     }
+    #[test]
+    fn old_tile_binary() {
+        // Saves from before stairs used a bool to tell walls and doors apart
+        let door = Tile::Door {
+            open: true,
+            rooms: [room_id(3), room_id(5)],
+        };
+        let mut buf = VecDeque::new();
+        false.to_binary(&mut buf).unwrap();
+        true.to_binary(&mut buf).unwrap();
+        true.to_binary(&mut buf).unwrap();
+        [room_id(3), room_id(5)].to_binary(&mut buf).unwrap();
+        assert_eq!(Tile::from_binary(&mut buf).unwrap(), Tile::Wall);
+        assert_eq!(Tile::from_binary(&mut buf).unwrap(), door);
+        assert_eq!(buf.len(), 0)
+    }
 }
@@ -1,4 +1,6 @@
 use crate::board::EnemyID;
+use crate::math::Vector;
+use crate::screen::Screen;
 use crate::state::Entity;
 use crate::state::State;
 use abes_nice_things::{FromBinary, ToBinary};
@@ -20,7 +22,7 @@ impl ContextMenu {
             .map(|(text, _, _)| text)
             .collect()
     }
-    pub fn render(state: &mut State, buffer: &mut impl Write) {
+    pub fn render(state: &mut State, screen: &mut Screen) {
         // Act options are purple
         // If we are using the context menu then make everything bold
         let style_base = if state.context_menu_inputs {
@@ -30,24 +32,19 @@ impl ContextMenu {
         };
 
         // We have the entire screen's height to work with
-        let start_column = state.screen_size.x - COLUMNS_NEEDED + 1;
+        let start_column = state.screen_size.x - COLUMNS_NEEDED;
         let context_menu = state.get_context_menu();
 
         // First we write the title
-        write!(
-            buffer,
-            "\x1b[1;{start_column}H{}{}\x1b[0m",
-            style_base.clone().yellow(),
-            context_menu.title
-        )
-        .unwrap();
+        screen.write(
+            Vector::new(start_column, 0),
+            &format!("{}{}", style_base.clone().yellow(), context_menu.title),
+        );
         // Then we write the separator
-        write!(
-            buffer,
-            "\x1b[2;{start_column}H╶{}╴",
-            "─".repeat(COLUMNS_NEEDED - 2)
-        )
-        .unwrap();
+        screen.write(
+            Vector::new(start_column, 1),
+            &format!("╶{}╴", "─".repeat(COLUMNS_NEEDED - 2)),
+        );
 
         // -2 for the title
         let available_rows = state.screen_size.y - 2;
@@ -67,7 +64,7 @@ impl ContextMenu {
         // Finally we can actually render them
         // took long enough, jeez
         for (row, index) in (start_index..(start_index + width)).enumerate() {
-            let row = row + 3; // 1 because visuals start at 1 and 1 becausse of title
+            let row = row + 2; // because of the title and separator
             let mut style = style_base.clone();
             if index == *selector {
                 style.background_red().intense(true);
@@ -79,12 +76,10 @@ impl ContextMenu {
                 style.dim(true);
             }
 
-            write!(
-                buffer,
-                "\x1b[{row};{start_column}H{}{}\x1b[0m",
-                style, options[index].0
-            )
-            .unwrap();
+            screen.write(
+                Vector::new(start_column, row),
+                &format!("{}{}", style, options[index].0),
+            );
         }
     }
 }
@@ -107,26 +102,36 @@ pub type Stack = Vec<(Option<Argument>, usize, ContextMenuID)>;
 pub enum Argument {
     EnemyID(EnemyID),
     Entity(Entity),
+    /// An index into the player's inventory
+    InventorySlot(usize),
 }
+// The discriminant used to be a bool, so the first two have to stay 0 and 1 for older saves to
+// load
 impl ToBinary for Argument {
     fn to_binary(&self, binary: &mut dyn Write) -> Result<()> {
         match self {
             Argument::EnemyID(id) => {
-                false.to_binary(binary)?;
+                0_u8.to_binary(binary)?;
                 id.to_binary(binary)
             }
             Argument::Entity(entity) => {
-                true.to_binary(binary)?;
+                1_u8.to_binary(binary)?;
                 entity.to_binary(binary)
             }
+            Argument::InventorySlot(slot) => {
+                2_u8.to_binary(binary)?;
+                slot.to_binary(binary)
+            }
         }
     }
 }
 impl FromBinary for Argument {
     fn from_binary(binary: &mut dyn std::io::prelude::Read) -> Result<Self> {
-        Ok(match bool::from_binary(binary)? {
-            false => Argument::EnemyID(EnemyID::from_binary(binary)?),
-            true => Argument::Entity(Entity::from_binary(binary)?),
+        Ok(match u8::from_binary(binary)? {
+            0 => Argument::EnemyID(EnemyID::from_binary(binary)?),
+            1 => Argument::Entity(Entity::from_binary(binary)?),
+            2 => Argument::InventorySlot(usize::from_binary(binary)?),
+            other => anyhow::bail!("Invalid context menu argument discriminant {other}"),
         })
     }
 }
@@ -145,6 +150,13 @@ impl Argument {
             None
         }
     }
+    fn inventory_slot(self) -> Option<usize> {
+        if let Argument::InventorySlot(slot) = self {
+            Some(slot)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -188,6 +200,11 @@ const DEBUG_MAIN: usize = 1;
 const SPECIFIC_ENEMY_DEBUG: usize = 2;
 const CHEAT_MAIN: usize = 3;
 const EFFECT_SETTER: usize = 4;
+const SAVE_MENU: usize = 5;
+const LOAD_MENU: usize = 6;
+const ABILITY_MENU: usize = 7;
+const INVENTORY_MENU: usize = 8;
+const ITEM_MENU: usize = 9;
 
 static CONTEXT_MENUS: &[ContextMenu] = &[
     // 0: Main menu
@@ -196,6 +213,16 @@ static CONTEXT_MENUS: &[ContextMenu] = &[
         title: "MAIN MENU:",
         get_options: |_| {
             vec![
+                (
+                    "Abilities".to_string(),
+                    Choice::Recurse(ABILITY_MENU, |_| None),
+                    true,
+                ),
+                (
+                    "Inventory".to_string(),
+                    Choice::Recurse(INVENTORY_MENU, |_| None),
+                    true,
+                ),
                 (
                     "Debug".to_string(),
                     Choice::Recurse(DEBUG_MAIN, |_| None),
@@ -234,6 +261,7 @@ static CONTEXT_MENUS: &[ContextMenu] = &[
                         state.board.to_binary(&mut buf).unwrap();
                         state.board = crate::board::Board::from_binary(&mut buf).unwrap();
                         assert_eq!(buf.len(), 0);
+                        crate::board::Board::update_field_of_view(state);
                         state.feedback = "Success".to_string();
                     })),
                     true,
@@ -368,6 +396,18 @@ static CONTEXT_MENUS: &[ContextMenu] = &[
                     Choice::Recurse(EFFECT_SETTER, |_| Some(Argument::Entity(Entity::Player))),
                     true,
                 ),
+                (
+                    "Set enemy effects".to_string(),
+                    Choice::Recurse(EFFECT_SETTER, |state| {
+                        Some(Argument::Entity(Entity::Enemy(
+                            state
+                                .board
+                                .get_enemy_at_position(state.player.selector)
+                                .unwrap(),
+                        )))
+                    }),
+                    state.board.is_enemy_at_position(state.player.selector),
+                ),
                 (
                     format!(
                         "No interact limit: {}",
@@ -404,26 +444,24 @@ static CONTEXT_MENUS: &[ContextMenu] = &[
                 (
                     "Teleport to selector".to_string(),
                     Choice::Act(Box::new(|state| {
-                        state.player.position = state.player.selector
+                        state.player.position = state.player.selector;
+                        crate::board::Board::update_field_of_view(state);
                     })),
                     true,
                 ),
+                (
+                    "Reveal map".to_string(),
+                    Choice::Act(Box::new(|state| state.board.reveal_all())),
+                    true,
+                ),
                 (
                     "Save".to_string(),
-                    Choice::Act(Box::new(|state| {
-                        let path = state.get_input("What file?".to_string());
-                        let mut file = std::fs::File::create(path).unwrap();
-                        state.to_binary(&mut file).unwrap();
-                    })),
+                    Choice::Recurse(SAVE_MENU, |_| None),
                     true,
                 ),
                 (
                     "Load".to_string(),
-                    Choice::Act(Box::new(|state| {
-                        let path = state.get_input("What file?".to_string());
-                        let mut file = std::fs::File::open(path).unwrap();
-                        *state = State::from_binary(&mut file).unwrap();
-                    })),
+                    Choice::Recurse(LOAD_MENU, |_| None),
                     true,
                 ),
             ]
@@ -434,22 +472,64 @@ static CONTEXT_MENUS: &[ContextMenu] = &[
     ContextMenu {
         title: "EFFECT SETTER",
         get_options: |state| {
+            let entity = state
+                .get_current_context_menu_argument()
+                .unwrap()
+                .entity()
+                .unwrap();
+            // The enemy could have died
+            let tracker = crate::effect::EffectTracker::of(state, entity);
             let mut options = Vec::new();
             for effect in 0..crate::effect::EFFECTS.len() {
                 let effect = crate::effect::EffectID::from_raw(effect as u8);
-                let current = state.player.effect_tracker.get(effect);
-                let time = match current {
-                    Some(time) => time.to_string(),
-                    None => "inf".to_string(),
+                let time = match tracker.map(|tracker| tracker.get(effect)) {
+                    Some(Some(time)) => time.to_string(),
+                    Some(None) => "inf".to_string(),
+                    None => "n/a".to_string(),
                 };
                 options.push((
                     format!("{}: {time}", effect.get().name),
                     Choice::Act(Box::new(move |state| {
-                        crate::effect::EffectTracker::prompt_set_time(
-                            state,
-                            Entity::Player,
-                            effect,
-                        );
+                        crate::effect::EffectTracker::prompt_set_time(state, entity, effect);
+                    })),
+                    tracker.is_some(),
+                ));
+            }
+            options
+        },
+    },
+    // 5: Saving
+    // no argument
+    ContextMenu {
+        title: "SAVE:",
+        get_options: |_| {
+            let mut options = vec![(
+                "New save".to_string(),
+                Choice::Act(Box::new(|state| {
+                    let name = state.get_input("Save name? ".to_string());
+                    if name.is_empty() {
+                        state.feedback = "Cancelled save".to_string();
+                        return;
+                    }
+                    if crate::save::slot_exists(&name)
+                        && !state.confirm(format!("{name} already exists, overwrite?"))
+                    {
+                        state.feedback = "Cancelled save".to_string();
+                        return;
+                    }
+                    state.save_to_slot(&name);
+                })),
+                true,
+            )];
+            for slot in crate::save::list_slots().into_iter() {
+                options.push((
+                    slot.clone(),
+                    Choice::Act(Box::new(move |state| {
+                        if state.confirm(format!("Overwrite {slot}?")) {
+                            state.save_to_slot(&slot);
+                        } else {
+                            state.feedback = "Cancelled save".to_string();
+                        }
                     })),
                     true,
                 ));
@@ -457,4 +537,128 @@ static CONTEXT_MENUS: &[ContextMenu] = &[
             options
         },
     },
+    // 6: Loading
+    // no argument
+    ContextMenu {
+        title: "LOAD:",
+        get_options: |_| {
+            let mut options = Vec::new();
+            for slot in crate::save::list_slots().into_iter() {
+                options.push((
+                    slot.clone(),
+                    Choice::Act(Box::new(move |state| state.load_from_slot(&slot))),
+                    true,
+                ));
+            }
+            if options.is_empty() {
+                options.push(("No saves".to_string(), Choice::Act(Box::new(|_| {})), false));
+            }
+            options
+        },
+    },
+    // 7: Abilities
+    // no argument
+    ContextMenu {
+        title: "ABILITIES:",
+        get_options: |state| {
+            crate::ability::AbilityID::iter()
+                .enumerate()
+                .map(|(index, id)| {
+                    let ability = id.get();
+                    (
+                        format!("{}: {} ({} energy)", index + 1, ability.name, ability.cost),
+                        Choice::Act(Box::new(move |state: &mut State| {
+                            state.act_from_menu(|state| crate::ability::use_ability(state, id));
+                        }) as Box<dyn Fn(&mut State)>),
+                        state.player.energy >= ability.cost,
+                    )
+                })
+                .collect()
+        },
+    },
+    // 8: Inventory
+    // no argument
+    ContextMenu {
+        title: "INVENTORY:",
+        get_options: |state| {
+            let mut options: Vec<(String, Choice, bool)> = state
+                .player
+                .inventory
+                .iter()
+                .map(|item| {
+                    (
+                        item.get().name.to_string(),
+                        // The selector is on the item being recursed into
+                        Choice::Recurse(ITEM_MENU, |state| {
+                            Some(Argument::InventorySlot(
+                                *state.get_context_menu_selector_mut(),
+                            ))
+                        }),
+                        true,
+                    )
+                })
+                .collect();
+            if options.is_empty() {
+                options.push(("Empty".to_string(), Choice::Act(Box::new(|_| {})), false));
+            }
+            // After the items so that the selector still lines up with the inventory
+            for slot in [crate::item::Slot::Weapon, crate::item::Slot::Armor] {
+                if let Some(item) = state.player.get_equipped(slot) {
+                    options.push((
+                        format!("Unequip {}", item.get().name),
+                        Choice::Act(Box::new(move |state| {
+                            state.act_from_menu(|state| crate::item::unequip(state, slot));
+                        })),
+                        true,
+                    ));
+                }
+            }
+            options
+        },
+    },
+    // 9: A specific item
+    // argument of InventorySlot for which item
+    ContextMenu {
+        title: "ITEM:",
+        get_options: |state| {
+            let slot = state
+                .get_current_context_menu_argument()
+                .unwrap()
+                .inventory_slot()
+                .unwrap();
+            let Some(item) = state.player.inventory.get(slot).map(|id| id.get()) else {
+                return vec![("n/a".to_string(), Choice::Act(Box::new(|_| {})), false)];
+            };
+            // Either way the slot is gone after so it goes back to the inventory
+            vec![
+                (
+                    format!("Equip {}", item.name),
+                    Choice::Act(Box::new(move |state| {
+                        if state.act_from_menu(|state| crate::item::equip(state, slot)) {
+                            state.leave_context_menu();
+                        }
+                    })),
+                    item.equipment.is_some(),
+                ),
+                (
+                    format!("Use {}", item.name),
+                    Choice::Act(Box::new(move |state| {
+                        if state.act_from_menu(|state| crate::item::use_item(state, slot)) {
+                            state.leave_context_menu();
+                        }
+                    })),
+                    item.on_use.is_some(),
+                ),
+                (
+                    format!("Drop {}", item.name),
+                    Choice::Act(Box::new(move |state| {
+                        if state.act_from_menu(|state| crate::item::drop(state, slot)) {
+                            state.leave_context_menu();
+                        }
+                    })),
+                    true,
+                ),
+            ]
+        },
+    },
 ];
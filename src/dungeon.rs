@@ -0,0 +1,106 @@
+use crate::board::AxisLength;
+use crate::board::Board;
+use crate::math::Vector;
+use crate::state::State;
+use abes_nice_things::{FromBinary, ToBinary};
+use anyhow::{Context, Result};
+
+/// How deep the player is and what is needed to generate the floors below
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Dungeon {
+    /// 0 is the first floor
+    pub depth: usize,
+    /// The size of the first floor
+    base_axis_length: AxisLength,
+    /// The enemy budget of the first floor
+    base_budget: usize,
+}
+impl ToBinary for Dungeon {
+    fn to_binary(&self, binary: &mut dyn std::io::prelude::Write) -> Result<()> {
+        self.depth.to_binary(binary)?;
+        self.base_axis_length.to_binary(binary)?;
+        self.base_budget.to_binary(binary)
+    }
+}
+impl FromBinary for Dungeon {
+    fn from_binary(binary: &mut dyn std::io::prelude::Read) -> Result<Self> {
+        Ok(Dungeon {
+            depth: usize::from_binary(binary)?,
+            base_axis_length: AxisLength::from_binary(binary)?,
+            base_budget: usize::from_binary(binary)?,
+        })
+    }
+}
+impl Dungeon {
+    /// The depth from which floors are always full size
+    const FULL_SIZE_DEPTH: usize = 3;
+    /// The enemy budget of the first floor when none is given
+    pub const DEFAULT_BUDGET: usize = 10000;
+    pub fn new(base_axis_length: AxisLength, base_budget: usize) -> Dungeon {
+        Dungeon {
+            depth: 0,
+            base_axis_length,
+            base_budget,
+        }
+    }
+    /// The size of the floor at the current depth
+    pub fn axis_length(&self) -> AxisLength {
+        if self.depth >= Dungeon::FULL_SIZE_DEPTH {
+            AxisLength::Full
+        } else {
+            self.base_axis_length
+        }
+    }
+    /// The enemy budget of the floor at the current depth, it goes up by half of the first
+    /// floor's every floor
+    pub fn budget(&self) -> usize {
+        self.base_budget + self.base_budget * self.depth / 2
+    }
+    /// Generates the next floor down and moves the player to its start. The player and the turn
+    /// count carry over, everything on the board is left behind
+    pub fn descend(state: &mut State) -> Result<()> {
+        let mut dungeon = state.dungeon;
+        dungeon.depth += 1;
+        let board = crate::board::map_gen::generate(
+            dungeon.axis_length(),
+            crate::MINIMUM_VIEWPORT,
+            dungeon.budget(),
+        )
+        .with_context(|| format!("Failed to generate depth {}", dungeon.depth))?;
+        state.dungeon = dungeon;
+        state.board = board;
+        state.player.position = Vector::new(1, 1);
+        state.player.selector = state.player.position;
+        state.reset_for_new_board();
+        Board::update_field_of_view(state);
+        state.feedback = format!("You went down to depth {}", dungeon.depth);
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::tile::Tile;
+    use crate::input::Input;
+    use crate::math::Direction;
+    #[test]
+    fn descend_stairs() {
+        let mut state = State::generate_headless(AxisLength::Small, 0, 0).unwrap();
+        // The stairs are kept behind the boss
+        let arena = state.board.get_boss_arena().unwrap();
+        let bounds = state.board[arena.room].get_bounds();
+        let stairs = Vector::new(bounds.right() - 1, bounds.bottom() - 1);
+        assert_eq!(state.board[stairs], Some(Tile::Stairs));
+
+        *state.board.get_enemy_mut(arena.boss) = None;
+        state.player.position = stairs + Direction::Left;
+        state.player.health = 30;
+        state.simulate([Input::Walk(Direction::Right)]);
+        assert_eq!(state.dungeon.depth, 1);
+        assert_eq!(state.player.position, Vector::new(1, 1));
+        // The player and turns carry over but the board is new
+        assert_eq!(state.player.health, 30);
+        assert_eq!(state.total_turns, 1);
+        assert_eq!(state.board.get_local_turn(), 0);
+    }
+}
@@ -1,6 +1,5 @@
 use crate::state::Entity;
 use crate::state::State;
-use abes_nice_things::require_debug;
 use abes_nice_things::{FromBinary, ToBinary};
 use anyhow::Result;
 
@@ -11,7 +10,15 @@ pub struct Effect {
     /// Whether or not the on_start should be reran on load or left alone
     run_on_start_on_load: bool,
     on_end: fn(&mut State, Entity),
+    /// Runs every turn the effect is active, before its timer counts down
+    on_turn: fn(&mut State, Entity),
+    /// Whether or not it is bad for whoever has it, casters only cast harmful effects
+    pub harmful: bool,
 }
+/// Does nothing, for effects which don't need one of the hooks
+const NO_HOOK: fn(&mut State, Entity) = |_, _| {};
+/// How far anything blinded can see
+pub const BLIND_SIGHT_RANGE: usize = 2;
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct EffectTracker {
     /// None is infinite time
@@ -19,19 +26,33 @@ pub struct EffectTracker {
     /// other is active for finite time
     inner: [Option<usize>; EFFECTS.len()],
 }
+// The number of effects is written first so that adding effects doesn't break older saves, any
+// effects missing from the save are inactive
 impl ToBinary for EffectTracker {
     fn to_binary(&self, binary: &mut dyn std::io::prelude::Write) -> Result<()> {
-        self.inner
-            .each_ref()
-            .map(|time| time.as_ref())
-            .to_binary(binary)
+        self.inner.len().to_binary(binary)?;
+        for time in self.inner.iter() {
+            time.as_ref().to_binary(binary)?;
+        }
+        Ok(())
     }
 }
 impl FromBinary for EffectTracker {
     fn from_binary(binary: &mut dyn std::io::prelude::Read) -> Result<Self> {
-        Ok(EffectTracker {
-            inner: <[Option<usize>; EFFECTS.len()]>::from_binary(binary)?,
-        })
+        // Before version 6 there was only confusion and the count wasn't written
+        let count = if crate::save::loading_version() >= 6 {
+            usize::from_binary(binary)?
+        } else {
+            1
+        };
+        if count > EFFECTS.len() {
+            anyhow::bail!("Save has {count} effects but only {} exist", EFFECTS.len());
+        }
+        let mut tracker = EffectTracker::default();
+        for time in tracker.inner.iter_mut().take(count) {
+            *time = <Option<usize>>::from_binary(binary)?;
+        }
+        Ok(tracker)
     }
 }
 impl Default for EffectTracker {
@@ -59,6 +80,17 @@ impl EffectTracker {
         }
         finished
     }
+    /// Runs the on_turn of every active effect, stopping early if an enemy dies from them
+    pub fn run_on_turns(state: &mut State, entity: Entity) {
+        for effect in EffectTracker::iter_effect_ids() {
+            let Some(tracker) = EffectTracker::of(state, entity) else {
+                return;
+            };
+            if tracker.has(effect) {
+                (effect.get().on_turn)(state, entity)
+            }
+        }
+    }
     pub fn run_on_ends(state: &mut State, entity: Entity, effects: Vec<EffectID>) {
         for effect in effects.into_iter() {
             (effect.get().on_end)(state, entity)
@@ -71,7 +103,7 @@ impl EffectTracker {
     pub fn set_effect_time(&mut self, effect: EffectID, time: Option<usize>) {
         self.inner[effect.to_raw() as usize] = time
     }
-    /// Does run on_start
+    /// Does run on_start, it is [EffectTracker::apply] with the time asked for
     pub fn prompt_set_time(state: &mut State, entity: Entity, effect: EffectID) {
         let time = loop {
             let input = state.get_input("How many turns? ".to_string());
@@ -88,18 +120,39 @@ impl EffectTracker {
                 },
             };
         };
+        EffectTracker::apply(state, entity, effect, time);
+    }
+    /// Sets how long the effect lasts, running on_start if it wasn't already active. Setting it
+    /// to 0 clears it
+    pub fn apply(state: &mut State, entity: Entity, effect: EffectID, time: Option<usize>) {
         if time == Some(0) {
             EffectTracker::clear(state, entity, effect);
             return;
         }
+        let Some(tracker) = EffectTracker::of_mut(state, entity) else {
+            return;
+        };
+        let was_active = tracker.has(effect);
+        // The time is set first so that the on_start sees the effect as active
+        tracker.set_effect_time(effect, time);
+        if !was_active {
+            (effect.get().on_start)(state, entity);
+        }
+    }
+    /// Gets the tracker of the entity, None if it is an enemy which is dead
+    pub fn of(state: &State, entity: Entity) -> Option<&EffectTracker> {
+        match entity {
+            Entity::Player => Some(&state.player.effect_tracker),
+            Entity::Enemy(id) => state.board[id].as_ref().map(|enemy| &enemy.effect_tracker),
+        }
+    }
+    /// Gets the tracker of the entity, None if it is an enemy which is dead
+    pub fn of_mut(state: &mut State, entity: Entity) -> Option<&mut EffectTracker> {
         match entity {
-            Entity::Player => {
-                if !state.player.effect_tracker.has(effect) {
-                    (effect.get().on_start)(state, entity);
-                }
-                state.player.effect_tracker.set_effect_time(effect, time);
-            }
-            Entity::Enemy(_) => abes_nice_things::require_debug!(todo!()),
+            Entity::Player => Some(&mut state.player.effect_tracker),
+            Entity::Enemy(id) => state.board[id]
+                .as_mut()
+                .map(|enemy| &mut enemy.effect_tracker),
         }
     }
     pub fn get(&self, effect: EffectID) -> Option<usize> {
@@ -109,14 +162,11 @@ impl EffectTracker {
         (0..EFFECTS.len()).map(|index| EffectID::from_raw(index as u8))
     }
     pub fn clear(state: &mut State, entity: Entity, effect: EffectID) {
-        match entity {
-            Entity::Player => {
-                if state.player.effect_tracker.has(effect) {
-                    state.player.effect_tracker.inner[effect.to_raw() as usize] = Some(0);
-                    (effect.get().on_end)(state, entity);
-                }
-            }
-            Entity::Enemy(_) => require_debug!(todo!()),
+        if let Some(tracker) = EffectTracker::of_mut(state, entity)
+            && tracker.has(effect)
+        {
+            tracker.inner[effect.to_raw() as usize] = Some(0);
+            (effect.get().on_end)(state, entity);
         }
     }
 }
@@ -124,6 +174,27 @@ impl EffectTracker {
 #[repr(u8)]
 pub enum EffectID {
     Confusion = 0,
+    Poison = 1,
+    Bleed = 2,
+    Regeneration = 3,
+    Haste = 4,
+    Slow = 5,
+    Blindness = 6,
+    Stun = 7,
+}
+impl ToBinary for EffectID {
+    fn to_binary(&self, binary: &mut dyn std::io::prelude::Write) -> Result<()> {
+        self.to_raw().to_binary(binary)
+    }
+}
+impl FromBinary for EffectID {
+    fn from_binary(binary: &mut dyn std::io::prelude::Read) -> Result<Self> {
+        let raw = u8::from_binary(binary)?;
+        if raw >= EFFECTS.len() as u8 {
+            anyhow::bail!("Invalid effect id {raw}");
+        }
+        Ok(EffectID::from_raw(raw))
+    }
 }
 impl EffectID {
     pub fn from_raw(raw: u8) -> EffectID {
@@ -145,17 +216,214 @@ impl EffectID {
         (self.get().on_start)(state, entity)
     }
 }
-pub static EFFECTS: &[Effect] = &[Effect {
-    name: "Confusion",
-    on_start: |_, entity| {
-        if let Entity::Player = entity {
-            print!("\x1b(0");
+/// Deals damage to the entity the same way an attack would
+fn damage(state: &mut State, entity: Entity, amount: usize) {
+    match entity {
+        Entity::Player => crate::player::Player::damage(state, amount),
+        Entity::Enemy(id) => {
+            (state.board[id].as_ref().unwrap().get_vtable().damage)(state, id, amount);
         }
+    }
+}
+fn get_health(state: &State, entity: Entity) -> usize {
+    match entity {
+        Entity::Player => state.player.health,
+        Entity::Enemy(id) => state.board[id].as_ref().unwrap().get_health(),
+    }
+}
+// The field of view only changes on increment, so it is redone right away when blindness starts
+// or ends to not show the wrong thing for a turn
+const UPDATE_SIGHT: fn(&mut State, Entity) = |state, entity| {
+    if let Entity::Player = entity {
+        crate::board::Board::update_field_of_view(state);
+    }
+};
+pub static EFFECTS: &[Effect] = &[
+    Effect {
+        name: "Confusion",
+        on_start: |state, entity| {
+            if let Entity::Player = entity
+                && !state.is_headless()
+            {
+                print!("\x1b(0");
+                // Everything already on screen has to be redrawn in the new character set
+                state.screen.invalidate();
+            }
+        },
+        run_on_start_on_load: true,
+        on_end: |state, entity| {
+            if let Entity::Player = entity
+                && !state.is_headless()
+            {
+                print!("\x1b(B");
+                state.screen.invalidate();
+            }
+        },
+        on_turn: NO_HOOK,
+        harmful: true,
     },
-    run_on_start_on_load: true,
-    on_end: |_, entity| {
-        if let Entity::Player = entity {
-            print!("\x1b(B");
-        }
+    Effect {
+        name: "Poison",
+        on_start: NO_HOOK,
+        run_on_start_on_load: false,
+        on_end: NO_HOOK,
+        // Poison hurts but it can't finish anything off
+        on_turn: |state, entity| {
+            if get_health(state, entity) > 1 {
+                damage(state, entity, 1);
+            }
+        },
+        harmful: true,
+    },
+    Effect {
+        name: "Bleed",
+        on_start: NO_HOOK,
+        run_on_start_on_load: false,
+        on_end: NO_HOOK,
+        on_turn: |state, entity| damage(state, entity, 1),
+        harmful: true,
+    },
+    Effect {
+        name: "Regeneration",
+        on_start: NO_HOOK,
+        run_on_start_on_load: false,
+        on_end: NO_HOOK,
+        on_turn: |state, entity| match entity {
+            Entity::Player => crate::player::Player::heal(state, 1),
+            Entity::Enemy(id) => state.board[id].as_mut().unwrap().heal(1),
+        },
+        harmful: false,
     },
-}];
+    // Haste, slow and stun are checked for when deciding how many actions happen in a turn, see
+    // State::finish_action and Enemy::actions_this_turn
+    Effect {
+        name: "Haste",
+        on_start: NO_HOOK,
+        run_on_start_on_load: false,
+        on_end: NO_HOOK,
+        on_turn: NO_HOOK,
+        harmful: false,
+    },
+    Effect {
+        name: "Slow",
+        on_start: NO_HOOK,
+        run_on_start_on_load: false,
+        on_end: NO_HOOK,
+        on_turn: NO_HOOK,
+        harmful: true,
+    },
+    Effect {
+        name: "Blindness",
+        on_start: UPDATE_SIGHT,
+        run_on_start_on_load: false,
+        on_end: UPDATE_SIGHT,
+        on_turn: NO_HOOK,
+        harmful: true,
+    },
+    Effect {
+        name: "Stun",
+        on_start: NO_HOOK,
+        run_on_start_on_load: false,
+        on_end: NO_HOOK,
+        on_turn: NO_HOOK,
+        harmful: true,
+    },
+];
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::enemy::{Enemy, VTableID};
+    use crate::input::Input;
+    use crate::math::Direction;
+    use crate::math::Vector;
+    use std::collections::VecDeque;
+    #[test]
+    fn enemy_effects() {
+        let mut state = State::blank_headless();
+        let id = state
+            .board
+            .add_enemy(Enemy::new(VTableID::Dummy, Vector::new(3, 3)));
+        EffectTracker::apply(&mut state, Entity::Enemy(id), EffectID::Confusion, Some(2));
+        Board::increment(&mut state);
+        assert!(
+            state.board[id]
+                .as_ref()
+                .unwrap()
+                .effect_tracker
+                .has(EffectID::Confusion)
+        );
+        Board::increment(&mut state);
+        assert!(
+            !state.board[id]
+                .as_ref()
+                .unwrap()
+                .effect_tracker
+                .has(EffectID::Confusion)
+        );
+
+        EffectTracker::apply(&mut state, Entity::Enemy(id), EffectID::Confusion, None);
+        let mut buf = VecDeque::new();
+        state.board[id]
+            .as_ref()
+            .unwrap()
+            .to_binary(&mut buf)
+            .unwrap();
+        let loaded = Enemy::from_binary(&mut buf).unwrap();
+        assert_eq!(loaded.effect_tracker.get(EffectID::Confusion), None);
+        assert_eq!(buf.len(), 0);
+    }
+    #[test]
+    fn status_effects() {
+        let mut state = State::blank_headless();
+        // Poison can't finish anyone off
+        state.player.health = 2;
+        EffectTracker::apply(&mut state, Entity::Player, EffectID::Poison, Some(3));
+        state.increment();
+        state.increment();
+        assert_eq!(state.player.health, 1);
+        EffectTracker::clear(&mut state, Entity::Player, EffectID::Poison);
+
+        // Regeneration stops at max health
+        state.player.health = state.player.max_health - 1;
+        EffectTracker::apply(&mut state, Entity::Player, EffectID::Regeneration, Some(3));
+        state.increment();
+        state.increment();
+        assert_eq!(state.player.health, state.player.max_health);
+
+        // Haste makes every other action free and slow makes them take two turns
+        EffectTracker::apply(&mut state, Entity::Player, EffectID::Haste, None);
+        let turns = state.total_turns;
+        state.simulate(std::iter::repeat_n(Input::SkipTurn, 2));
+        assert_eq!(state.total_turns, turns + 1);
+        EffectTracker::clear(&mut state, Entity::Player, EffectID::Haste);
+        EffectTracker::apply(&mut state, Entity::Player, EffectID::Slow, None);
+        state.simulate([Input::SkipTurn]);
+        assert_eq!(state.total_turns, turns + 3);
+        EffectTracker::clear(&mut state, Entity::Player, EffectID::Slow);
+
+        // Stunned players can't move
+        let position = state.player.position;
+        EffectTracker::apply(&mut state, Entity::Player, EffectID::Stun, Some(1));
+        state.simulate([Input::Walk(Direction::Right)]);
+        assert_eq!(state.player.position, position);
+        assert!(!state.player.effect_tracker.has(EffectID::Stun));
+
+        // Enemies get more or fewer actions
+        let id = state
+            .board
+            .add_enemy(Enemy::new(VTableID::Dummy, Vector::new(3, 3)));
+        EffectTracker::apply(&mut state, Entity::Enemy(id), EffectID::Haste, None);
+        assert_eq!(Enemy::actions_this_turn(&state, id), 2);
+        EffectTracker::apply(&mut state, Entity::Enemy(id), EffectID::Slow, None);
+        assert_eq!(Enemy::actions_this_turn(&state, id), 1);
+        EffectTracker::apply(&mut state, Entity::Enemy(id), EffectID::Stun, None);
+        assert_eq!(Enemy::actions_this_turn(&state, id), 0);
+
+        let mut buf = VecDeque::new();
+        state.player.effect_tracker.to_binary(&mut buf).unwrap();
+        let loaded = EffectTracker::from_binary(&mut buf).unwrap();
+        assert_eq!(loaded, state.player.effect_tracker);
+        assert_eq!(buf.len(), 0);
+    }
+}
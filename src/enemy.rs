@@ -1,45 +1,84 @@
-// Place mod for enemies here
-pub mod basic;
-pub mod dummy;
-// Put the vtable here
-pub static VTABLES: [VTable; 2] = [dummy::VTABLE, basic::VTABLE];
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
-#[repr(u8)]
-// Register the vtable here, make sure you correctly put its index
-pub enum VTableID {
-    Dummy = 0,
-    Basic = 1,
+/// Generates the modules, [VTABLES], [VTableID] and [CONVERTERS] from the list of enemy types so
+/// that their indices always line up
+macro_rules! register_enemies {
+    ($($name:ident => $module:ident $(($state:ty))?),* $(,)?) => {
+        $(pub mod $module;)*
+        pub static VTABLES: [VTable; [$(stringify!($name)),*].len()] = [$($module::VTABLE),*];
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        #[repr(u8)]
+        pub enum VTableID {
+            $($name),*
+        }
+        static CONVERTERS: [Converters; VTABLES.len()] =
+            [$(register_enemies!(@converters $($state)?)),*];
+    };
+    (@converters) => {
+        NO_OP_CONVERTERS
+    };
+    (@converters $state:ty) => {
+        converters::<$state>()
+    };
+}
+// To add an enemy type, make a module in src/enemy/ with a `pub static VTABLE: VTable` and add it
+// here. If its init makes a state other than (), put the state's type in brackets after it so
+// that it gets saved. Everything else is generated, in the order listed. Saves store the index
+// so new types go at the end.
+register_enemies! {
+    Dummy => dummy,
+    Basic => basic,
+    Ranged => ranged (ranged::Aim),
+    Caster => caster (caster::Spellbook),
+    Boss => boss (boss::BossState),
 }
-// Add in the required logic for it
 impl Enemy {
+    /// Picks a random type weighted by spawn weight out of the types which fit in the budget and
+    /// are at most max_tier, then takes its cost out of the budget.
+    ///
     /// Returns None if it was unable to figure out a possible enemy type to meet restrictions
     pub fn pick_vtable_from_budget(
         budget: &mut usize,
         max_tier: Option<usize>,
     ) -> Option<VTableID> {
-        if *budget < VTableID::Basic.get_vtable().budget_cost {
-            None
-        } else {
-            *budget -= VTableID::Basic.get_vtable().budget_cost;
-            Some(VTableID::Basic)
+        let candidates: Vec<VTableID> = (0..VTABLES.len() as u8)
+            .map(VTableID::from_raw)
+            .filter(|id| {
+                let vtable = id.get_vtable();
+                vtable.spawn_weight > 0
+                    && vtable.budget_cost <= *budget
+                    && max_tier.is_none_or(|max_tier| vtable.tier <= max_tier)
+            })
+            .collect();
+        let total_weight: usize = candidates
+            .iter()
+            .map(|id| id.get_vtable().spawn_weight)
+            .sum();
+        if total_weight == 0 {
+            return None;
+        }
+        let mut roll = usize::random() % total_weight;
+        for id in candidates {
+            let weight = id.get_vtable().spawn_weight;
+            if roll < weight {
+                *budget -= id.get_vtable().budget_cost;
+                return Some(id);
+            }
+            roll -= weight;
         }
+        unreachable!("The roll is always less than the total weight")
     }
 }
-static CONVERTERS: [(
-    fn(&Box<dyn Any + Send>, &mut dyn Write) -> std::io::Result<()>,
-    fn(&mut dyn std::io::Read) -> std::io::Result<Box<dyn Any + Send>>,
-); VTABLES.len()] = [NO_OP_CONVERTERS, NO_OP_CONVERTERS];
-// And you're done
 
 use crate::Vector;
 use crate::board::Board;
 use crate::board::EnemyID;
+use crate::effect::EffectID;
 use crate::math::Direction;
+use crate::random::Random;
 use crate::state::*;
 use abes_nice_things::PrimAs;
 use abes_nice_things::Style;
 use abes_nice_things::{FromBinary, ToBinary};
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::any::Any;
 use std::io::Write;
 
@@ -62,6 +101,7 @@ pub struct Enemy {
     /// The position used in intra room pathfinding
     logical_position: Vector<f64>,
     windup_time: usize,
+    pub effect_tracker: crate::effect::EffectTracker,
     /// The file for enemy specific logging
     /// This does NOT get saved when writing to a file
     log: Option<std::fs::File>,
@@ -76,14 +116,15 @@ impl ToBinary for Enemy {
         self.end_goal.as_ref().to_binary(binary)?;
         self.flags.to_binary(binary)?;
         self.logical_position.to_binary(binary)?;
-        self.windup_time.to_binary(binary)
+        self.windup_time.to_binary(binary)?;
+        self.effect_tracker.to_binary(binary)
         // log does NOT get saved
     }
 }
 impl FromBinary for Enemy {
     fn from_binary(binary: &mut dyn std::io::prelude::Read) -> Result<Self> {
         let vtable_id = VTableID::from_binary(binary)?;
-        Ok(Enemy {
+        let mut enemy = Enemy {
             state: (CONVERTERS[vtable_id.to_inner() as usize].1)(binary)?,
             health: usize::from_binary(binary)?,
             position: <Vector<usize>>::from_binary(binary)?,
@@ -93,8 +134,14 @@ impl FromBinary for Enemy {
             flags: Flags::from_binary(binary)?,
             logical_position: <Vector<f64>>::from_binary(binary)?,
             windup_time: usize::from_binary(binary)?,
+            effect_tracker: crate::effect::EffectTracker::default(),
             log: None, // log does NOT get saved
-        })
+        };
+        // Before version 5 enemies had no effects
+        if crate::save::loading_version() >= 5 {
+            enemy.effect_tracker = crate::effect::EffectTracker::from_binary(binary)?;
+        }
+        Ok(enemy)
     }
 }
 impl Enemy {
@@ -110,6 +157,7 @@ impl Enemy {
             flags: Flags::new(),
             logical_position: position.prim_as() + 0.5,
             windup_time: 0,
+            effect_tracker: crate::effect::EffectTracker::default(),
             log: None,
         }
     }
@@ -136,6 +184,72 @@ impl Enemy {
     pub fn get_vtable(&self) -> &'static VTable {
         self.vtable_id.get_vtable()
     }
+    /// Wakes the enemy up if it can see the player or it heard a noise, returns if it is awake
+    pub fn try_wake(state: &mut State, id: EnemyID) -> bool {
+        let this = state.board[id].as_ref().unwrap();
+        if this.flags.is_awake() {
+            return true;
+        }
+        let position = this.position;
+        if Enemy::can_see_player(state, position, this.sight_range(SIGHT_RANGE))
+            || Enemy::can_hear_noise(state, position)
+        {
+            // Wakey wakey
+            state.board[id].as_mut().unwrap().flags.wake();
+            return true;
+        }
+        false
+    }
+    /// How far it can see given how far it could normally see, blindness cuts it down
+    pub fn sight_range(&self, range: usize) -> usize {
+        if self.effect_tracker.has(EffectID::Blindness) {
+            range.min(crate::effect::BLIND_SIGHT_RANGE)
+        } else {
+            range
+        }
+    }
+    /// How many times it gets to think and move this turn. Stunned enemies do nothing, hasted
+    /// enemies go twice and slowed enemies only go on even turns
+    pub fn actions_this_turn(state: &State, id: EnemyID) -> usize {
+        let tracker = &state.board[id].as_ref().unwrap().effect_tracker;
+        if tracker.has(EffectID::Stun) {
+            return 0;
+        }
+        match (tracker.has(EffectID::Haste), tracker.has(EffectID::Slow)) {
+            (true, false) => 2,
+            (false, true) => (state.board.get_local_turn() % 2 == 0) as usize,
+            _ => 1,
+        }
+    }
+    /// Checks if the player is within range and nothing is blocking the view of them
+    pub fn can_see_player(state: &State, position: Vector<usize>, range: usize) -> bool {
+        position.is_near(state.player.position, range)
+            && crate::raycast::RayCast::new(position, state.player.position)
+                .can_hit_player(true)
+                .can_hit_enemy(false)
+                .max_range(Some(range * 2))
+                .resolve(state)
+                .0
+                == Some(MapObject::Player)
+    }
+    /// Checks if any of the noises made last turn were loud enough to be heard from the position
+    pub fn can_hear_noise(state: &State, position: Vector<usize>) -> bool {
+        let rooms = state.board.get_possible_room_ids_at_position(position);
+        state.board.get_heard_noises().iter().any(|noise| {
+            let same_room = state
+                .board
+                .get_possible_room_ids_at_position(noise.position)
+                .iter()
+                .any(|room_id| rooms.contains(room_id));
+            // Walls muffle sound
+            let loudness = if same_room {
+                noise.loudness
+            } else {
+                noise.loudness / 2
+            };
+            position.is_near(noise.position, loudness)
+        })
+    }
     pub fn intra_room_pathfind(state: &mut State, id: EnemyID) {
         let this = state.board.get_enemy_mut(id).as_mut().unwrap();
         if this.logical_position.prim_as() != this.position {
@@ -297,6 +411,25 @@ impl Enemy {
     pub fn get_position(&self) -> Vector<usize> {
         self.position
     }
+    pub fn get_health(&self) -> usize {
+        self.health
+    }
+    /// Heals up to its starting health
+    pub fn heal(&mut self, amount: usize) {
+        self.health = (self.health + amount).min(self.get_vtable().starting_health);
+    }
+    /// Gets the state as the type its vtable made it with, panics if it is a different type
+    pub fn get_state<T: EnemyState>(&self) -> &T {
+        (*self.state)
+            .downcast_ref()
+            .expect("Enemy state was not the requested type")
+    }
+    /// Gets the state as the type its vtable made it with, panics if it is a different type
+    pub fn get_state_mut<T: EnemyState>(&mut self) -> &mut T {
+        (*self.state)
+            .downcast_mut()
+            .expect("Enemy state was not the requested type")
+    }
     // cold because this is purely a debugging tool and should not be assumed to run so that
     // efficiency is preserved
     #[cold]
@@ -316,6 +449,8 @@ impl Enemy {
         self.log.is_some()
     }
 }
+/// How far away enemies can see the player from
+const SIGHT_RANGE: usize = 10;
 /// Where enemy type specific logic is stored as well as some constants
 #[derive(Clone, Copy, Debug)]
 pub struct VTable {
@@ -331,9 +466,16 @@ pub struct VTable {
     pub damage: fn(&mut State, EnemyID, usize) -> bool,
     budget_cost: usize,
     pub tier: usize,
+    /// How likely it is to be picked when spawning compared to the other types, 0 means it is
+    /// never spawned from budget
+    spawn_weight: usize,
 }
 impl VTable {
     const DEFAULT_INIT: fn() -> Box<dyn Any + Send> = || Box::new(());
+    /// Creates an init which starts the state as the default of T
+    pub const fn default_init<T: EnemyState + Default>() -> fn() -> Box<dyn Any + Send> {
+        default_state::<T>
+    }
     const DEFAULT_DAMAGE: fn(&mut State, EnemyID, usize) -> bool = |state, id, damage| {
         let this = state.board.get_enemy_mut(id).as_mut().unwrap();
         if damage >= this.health {
@@ -485,7 +627,11 @@ impl ToBinary for VTableID {
 }
 impl FromBinary for VTableID {
     fn from_binary(binary: &mut dyn std::io::prelude::Read) -> Result<Self> {
-        Ok(VTableID::from_raw(u8::from_binary(binary)?))
+        let raw = u8::from_binary(binary)?;
+        if raw >= VTABLES.len() as u8 {
+            anyhow::bail!("Invalid enemy type {raw}");
+        }
+        Ok(VTableID::from_raw(raw))
     }
 }
 impl PartialOrd for VTableID {
@@ -498,7 +644,113 @@ impl Ord for VTableID {
         self.to_inner().cmp(&other.to_inner())
     }
 }
-static NO_OP_CONVERTERS: (
-    fn(&Box<dyn Any + Send>, &mut dyn Write) -> std::io::Result<()>,
-    fn(&mut dyn std::io::Read) -> std::io::Result<Box<dyn Any + Send>>,
-) = (|_, _| Ok(()), |_| Ok(Box::new(())));
+fn default_state<T: EnemyState + Default>() -> Box<dyn Any + Send> {
+    Box::new(T::default())
+}
+
+/// Anything which can be the state of an enemy and be saved
+pub trait EnemyState: ToBinary + FromBinary + Any + Send {}
+impl<T: ToBinary + FromBinary + Any + Send> EnemyState for T {}
+
+/// How the state of an enemy type is written and read
+pub type Converters = (
+    fn(&Box<dyn Any + Send>, &mut dyn Write) -> Result<()>,
+    fn(&mut dyn std::io::Read) -> Result<Box<dyn Any + Send>>,
+);
+/// For enemy types which have no state (Box<()>), nothing gets written
+const NO_OP_CONVERTERS: Converters = (|_, _| Ok(()), |_| Ok(Box::new(())));
+/// Creates the converters for an enemy type whose state is T. The init in its vtable MUST create
+/// a T
+pub const fn converters<T: EnemyState>() -> Converters {
+    (save_state::<T>, load_state::<T>)
+}
+fn save_state<T: EnemyState>(state: &Box<dyn Any + Send>, binary: &mut dyn Write) -> Result<()> {
+    (**state)
+        .downcast_ref::<T>()
+        .context("Enemy state was not the type its converters expected")?
+        .to_binary(binary)
+}
+fn load_state<T: EnemyState>(binary: &mut dyn std::io::Read) -> Result<Box<dyn Any + Send>> {
+    Ok(Box::new(T::from_binary(binary)?))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::AxisLength;
+    use std::collections::VecDeque;
+    #[test]
+    fn typed_converters() {
+        let (save, load) = converters::<u32>();
+        let mut buf = VecDeque::new();
+        for test in [0_u32, 7, u32::MAX] {
+            let state: Box<dyn Any + Send> = Box::new(test);
+            save(&state, &mut buf).unwrap();
+            assert_eq!(load(&mut buf).unwrap().downcast_ref::<u32>(), Some(&test));
+        }
+        assert_eq!(buf.len(), 0);
+    }
+    #[test]
+    fn pick_vtable_restrictions() {
+        crate::random::seed(0);
+        for _ in 0..100 {
+            let mut budget = 2;
+            let picked = Enemy::pick_vtable_from_budget(&mut budget, None).unwrap();
+            assert!(picked.get_vtable().budget_cost <= 2);
+            assert_eq!(budget, 2 - picked.get_vtable().budget_cost);
+
+            let mut budget = 100;
+            let picked = Enemy::pick_vtable_from_budget(&mut budget, Some(0)).unwrap();
+            assert_eq!(picked.get_vtable().tier, 0);
+        }
+        assert_eq!(Enemy::pick_vtable_from_budget(&mut 0, None), None);
+    }
+    #[test]
+    fn registered_converters_match_init() {
+        for (vtable, (save, load)) in VTABLES.iter().zip(CONVERTERS.iter()) {
+            let state = (vtable.init)();
+            let mut buf = VecDeque::new();
+            save(&state, &mut buf).unwrap();
+            let loaded = load(&mut buf).unwrap();
+            assert_eq!((*state).type_id(), (*loaded).type_id());
+            assert_eq!(buf.len(), 0);
+        }
+    }
+    #[test]
+    fn invalid_vtable_id() {
+        let mut buf = VecDeque::new();
+        (VTABLES.len() as u8).to_binary(&mut buf).unwrap();
+        assert!(VTableID::from_binary(&mut buf).is_err());
+    }
+    #[test]
+    fn mismatched_converters() {
+        let (save, _) = converters::<u32>();
+        let state: Box<dyn Any + Send> = Box::new(0_u8);
+        assert!(save(&state, &mut VecDeque::new()).is_err());
+    }
+    #[test]
+    fn enemy_wake_up() {
+        use crate::board::Noise;
+        use crate::board::tile::Tile;
+        let mut state = State::blank_headless();
+        for y in 0..AxisLength::Small.to_inner() {
+            state.board[Vector::new(5, y)] = Some(Tile::Wall);
+        }
+        let seeing = state
+            .board
+            .add_enemy(Enemy::new(VTableID::Dummy, Vector::new(3, 3)));
+        let hidden = state
+            .board
+            .add_enemy(Enemy::new(VTableID::Dummy, Vector::new(7, 1)));
+        assert!(Enemy::try_wake(&mut state, seeing));
+        assert!(!Enemy::try_wake(&mut state, hidden));
+
+        // Too quiet to be heard through the wall
+        state.board.make_noise(Vector::new(4, 1), Noise::DOOR);
+        Board::increment(&mut state);
+        assert!(!Enemy::try_wake(&mut state, hidden));
+
+        state.board.make_noise(Vector::new(4, 1), Noise::COMBAT);
+        Board::increment(&mut state);
+        assert!(Enemy::try_wake(&mut state, hidden));
+    }
+}
@@ -9,35 +9,13 @@ pub static VTABLE: VTable = VTable {
     damage: VTable::DEFAULT_DAMAGE,
     budget_cost: 1,
     tier: 0,
+    spawn_weight: 10,
 };
 const SMACK_RANGE: usize = 1;
 fn think(state: &mut State, id: super::EnemyID) {
     // If we aren't awake then try to wake up
-    if !state.board[id].as_ref().unwrap().flags.is_awake() {
-        let mut wake_distance = (u8::random() & 0b111) as usize;
-        if !state
-            .board
-            .get_possible_room_ids_at_position(state.board[id].as_ref().unwrap().get_position())
-            .iter()
-            .any(|room_id| {
-                state
-                    .board
-                    .get_possible_room_ids_at_position(state.player.position)
-                    .contains(room_id)
-            })
-        {
-            // We are not in the same room :(
-            wake_distance /= 2;
-        }
-        if state.board[id]
-            .as_ref()
-            .unwrap()
-            .get_position()
-            .is_near(state.player.position, wake_distance)
-        {
-            // Wakey wakey
-            state.board[id].as_mut().unwrap().flags.wake()
-        }
+    if !super::Enemy::try_wake(state, id) {
+        return;
     }
 
     // Since we are awake let's get killing
@@ -52,6 +30,9 @@ fn think(state: &mut State, id: super::EnemyID) {
             this.flags.set_pathing(true);
             this.flags.set_windup(super::WindupState::None);
             if state.player.position.is_near(this.position, SMACK_RANGE) {
+                state
+                    .board
+                    .make_noise(state.player.position, crate::board::Noise::COMBAT);
                 crate::player::Player::damage(state, (u8::random() & 0b111) as usize + 1);
                 return;
             }
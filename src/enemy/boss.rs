@@ -0,0 +1,237 @@
+use super::Enemy;
+use super::VTable;
+use super::VTableID;
+use crate::Vector;
+use crate::board::Board;
+use crate::board::Noise;
+use crate::math::Direction;
+use crate::random::Random;
+use crate::state::*;
+use abes_nice_things::{FromBinary, ToBinary};
+use anyhow::{Result, bail};
+pub static VTABLE: VTable = VTable {
+    starting_health: 24,
+    is_boss: true,
+    init: VTable::default_init::<BossState>(),
+    think,
+    damage: VTable::DEFAULT_DAMAGE,
+    // Bosses are placed by map generation, never bought with budget
+    budget_cost: 0,
+    tier: 9999,
+    spawn_weight: 0,
+};
+const SMACK_RANGE: usize = 1;
+/// Turns between shots while shooting
+const SHOT_COOLDOWN: usize = 3;
+/// Turns between summons while enraged
+const SUMMON_COOLDOWN: usize = 10;
+/// How many minions get summoned at once at most
+const SUMMON_COUNT: usize = 2;
+/// The boss gets more dangerous as it loses health
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Phase {
+    /// Walks up and hits hard
+    #[default]
+    Brawler = 0,
+    /// Also shoots when it can see the player
+    Gunner = 1,
+    /// Winds up faster and summons minions
+    Enraged = 2,
+}
+impl Phase {
+    fn from_health(health: usize) -> Phase {
+        if health * 3 > VTABLE.starting_health * 2 {
+            Phase::Brawler
+        } else if health * 3 > VTABLE.starting_health {
+            Phase::Gunner
+        } else {
+            Phase::Enraged
+        }
+    }
+    fn windup(self) -> usize {
+        match self {
+            Phase::Enraged => 1,
+            _ => 2,
+        }
+    }
+    fn announcement(self) -> &'static str {
+        match self {
+            Phase::Brawler => "The boss readies itself",
+            Phase::Gunner => "The boss draws a weapon",
+            Phase::Enraged => "The boss becomes enraged",
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BossState {
+    phase: Phase,
+    shot_cooldown: usize,
+    summon_cooldown: usize,
+    /// Where the shot being wound up is aimed
+    aim: Option<Vector<usize>>,
+}
+impl ToBinary for BossState {
+    fn to_binary(&self, binary: &mut dyn std::io::Write) -> Result<()> {
+        (self.phase as u8).to_binary(binary)?;
+        self.shot_cooldown.to_binary(binary)?;
+        self.summon_cooldown.to_binary(binary)?;
+        self.aim.as_ref().to_binary(binary)
+    }
+}
+impl FromBinary for BossState {
+    fn from_binary(binary: &mut dyn std::io::Read) -> Result<Self> {
+        Ok(BossState {
+            phase: match u8::from_binary(binary)? {
+                0 => Phase::Brawler,
+                1 => Phase::Gunner,
+                2 => Phase::Enraged,
+                other => bail!("Invalid boss phase {other}"),
+            },
+            shot_cooldown: usize::from_binary(binary)?,
+            summon_cooldown: usize::from_binary(binary)?,
+            aim: <Option<Vector<usize>>>::from_binary(binary)?,
+        })
+    }
+}
+fn think(state: &mut State, id: super::EnemyID) {
+    // Bosses wait in their arena until the fight starts
+    if !state.board[id].as_ref().unwrap().flags.is_awake() {
+        return;
+    }
+
+    let this = state.board[id].as_mut().unwrap();
+    let position = this.position;
+    let shot_range = this.sight_range(super::ranged::RANGE);
+    let phase = Phase::from_health(this.health);
+    let boss_state = this.get_state_mut::<BossState>();
+    if boss_state.phase != phase {
+        boss_state.phase = phase;
+        state.feedback = phase.announcement().to_string();
+    }
+    let this = state.board[id].as_mut().unwrap();
+    let boss_state = this.get_state_mut::<BossState>();
+    boss_state.shot_cooldown = boss_state.shot_cooldown.saturating_sub(1);
+    boss_state.summon_cooldown = boss_state.summon_cooldown.saturating_sub(1);
+
+    // Finishing whatever was wound up
+    let windup = this.flags.get_windup();
+    if !windup.is_none() {
+        this.windup_time -= 1;
+        if this.windup_time != 0 {
+            return;
+        }
+        this.flags.set_pathing(true);
+        this.flags.set_windup(super::WindupState::None);
+        if windup.is_physical() {
+            if state.player.position.is_near(position, SMACK_RANGE) {
+                state.board.make_noise(state.player.position, Noise::COMBAT);
+                crate::player::Player::damage(state, (u8::random() & 0b11) as usize + 2);
+            }
+        } else if windup.is_ranged() {
+            let aim = this.get_state_mut::<BossState>().aim.take();
+            if let Some(aim) = aim {
+                super::ranged::shoot(state, position, aim, (u8::random() & 0b11) as usize + 1);
+            }
+        } else if windup.is_magical() {
+            summon(state, position);
+        }
+        return;
+    }
+
+    // Picking what to do next
+    let boss_state = *this.get_state_mut::<BossState>();
+    let (windup, time) = if state.player.position.is_near(position, SMACK_RANGE) {
+        (super::WindupState::Physical, phase.windup())
+    } else if phase == Phase::Enraged && boss_state.summon_cooldown == 0 {
+        this.get_state_mut::<BossState>().summon_cooldown = SUMMON_COOLDOWN;
+        (super::WindupState::Magical, phase.windup())
+    } else if phase >= Phase::Gunner
+        && boss_state.shot_cooldown == 0
+        && Enemy::can_see_player(state, position, shot_range)
+    {
+        let player_position = state.player.position;
+        let boss_state = state.board[id]
+            .as_mut()
+            .unwrap()
+            .get_state_mut::<BossState>();
+        boss_state.shot_cooldown = SHOT_COOLDOWN;
+        boss_state.aim = Some(player_position);
+        (super::WindupState::Ranged, phase.windup())
+    } else {
+        // Nothing to do but chase
+        state.board[id].as_mut().unwrap().end_goal = Some(state.player.position);
+        return;
+    };
+    let this = state.board[id].as_mut().unwrap();
+    this.end_goal = None;
+    this.move_target = None;
+    this.flags.set_pathing(false);
+    this.flags.set_windup(windup);
+    this.windup_time = time;
+}
+/// Summons awake minions next to the position
+fn summon(state: &mut State, position: Vector<usize>) {
+    let mut summoned = 0;
+    for direction in [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ] {
+        if summoned == SUMMON_COUNT {
+            break;
+        }
+        // Minions need to be in a room for pathfinding
+        if !Board::enemy_can_move(state, position, direction)
+            || state
+                .board
+                .get_room_id_of_coord(position + direction)
+                .is_none()
+        {
+            continue;
+        }
+        let mut minion = Enemy::new(VTableID::Basic, position + direction);
+        minion.flags.wake();
+        state.board.add_enemy(minion);
+        summoned += 1;
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::AxisLength;
+    #[test]
+    fn boss_arena() {
+        crate::random::seed(0);
+        let mut state = State::generate_headless(AxisLength::Small, 0, 0).unwrap();
+        let arena = state.board.get_boss_arena().unwrap();
+        let door = state.board[arena.room].connections[0].0;
+
+        // An enemy in a doorway keeps the arena from locking
+        let blocker = state.board.add_enemy(Enemy::new(VTableID::Basic, door));
+        let boss_position = state.board[arena.boss].as_ref().unwrap().get_position();
+        state.player.position = boss_position + Direction::Left;
+        state.increment();
+        assert!(!state.board.get_boss_arena().unwrap().locked);
+        assert!(!state.board.is_door_locked(door));
+
+        // Once it is gone every door closes and they lock
+        *state.board.get_enemy_mut(blocker) = None;
+        state.increment();
+        assert!(state.board.get_boss_arena().unwrap().locked);
+        for (door, _) in &state.board[arena.room].connections {
+            assert!(matches!(
+                state.board[*door],
+                Some(crate::board::tile::Tile::Door { open: false, .. })
+            ));
+        }
+        assert!(state.board.is_door_locked(door));
+
+        // Killing the boss unlocks them
+        *state.board.get_enemy_mut(arena.boss) = None;
+        state.increment();
+        assert!(!state.board.get_boss_arena().unwrap().locked);
+        assert!(!state.board.is_door_locked(door));
+    }
+}
@@ -0,0 +1,118 @@
+use super::Enemy;
+use super::VTable;
+use crate::effect::EffectID;
+use crate::effect::EffectTracker;
+use crate::random::Random;
+use crate::state::*;
+use abes_nice_things::{FromBinary, ToBinary};
+use anyhow::Result;
+pub static VTABLE: VTable = VTable {
+    starting_health: 2,
+    is_boss: false,
+    init: VTable::default_init::<Spellbook>(),
+    think,
+    damage: VTable::DEFAULT_DAMAGE,
+    budget_cost: 4,
+    tier: 2,
+    spawn_weight: 2,
+};
+/// How far away it can cast from, it needs to see the player the whole time
+const RANGE: usize = 6;
+/// The turns spent winding up, this is the player's chance to get out of sight
+const WINDUP: usize = 3;
+/// The turns before it can start casting again
+const COOLDOWN: usize = 8;
+/// How long the effects it casts last
+const SPELL_DURATION: usize = 5;
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Spellbook {
+    cooldown: usize,
+    /// The spell being wound up
+    casting: Option<EffectID>,
+}
+impl ToBinary for Spellbook {
+    fn to_binary(&self, binary: &mut dyn std::io::Write) -> Result<()> {
+        self.cooldown.to_binary(binary)?;
+        self.casting.as_ref().to_binary(binary)
+    }
+}
+impl FromBinary for Spellbook {
+    fn from_binary(binary: &mut dyn std::io::Read) -> Result<Self> {
+        Ok(Spellbook {
+            cooldown: usize::from_binary(binary)?,
+            casting: <Option<EffectID>>::from_binary(binary)?,
+        })
+    }
+}
+fn think(state: &mut State, id: super::EnemyID) {
+    if !Enemy::try_wake(state, id) {
+        return;
+    }
+
+    let position = state.board[id].as_ref().unwrap().position;
+    let range = state.board[id].as_ref().unwrap().sight_range(RANGE);
+    let this = state.board[id].as_mut().unwrap();
+    // Are we casting?
+    if this.flags.get_windup().is_magical() {
+        this.windup_time -= 1;
+        if this.windup_time == 0 {
+            this.flags.set_pathing(true);
+            this.flags.set_windup(super::WindupState::None);
+            let spellbook = this.get_state_mut::<Spellbook>();
+            spellbook.cooldown = COOLDOWN;
+            let spell = spellbook.casting.take();
+            // If they got out of sight then it fizzles
+            if let Some(spell) = spell
+                && Enemy::can_see_player(state, position, range)
+            {
+                EffectTracker::apply(state, Entity::Player, spell, Some(SPELL_DURATION));
+                state.feedback = format!("You were hit by {}", spell.get().name);
+            }
+        }
+        return;
+    }
+
+    let spellbook = this.get_state_mut::<Spellbook>();
+    spellbook.cooldown = spellbook.cooldown.saturating_sub(1);
+    let ready = spellbook.cooldown == 0;
+    // Only bother casting curses the player doesn't already have
+    let spells: Vec<EffectID> = EffectTracker::iter_effect_ids()
+        .filter(|effect| effect.get().harmful && !state.player.effect_tracker.has(*effect))
+        .collect();
+    if ready && !spells.is_empty() && Enemy::can_see_player(state, position, range) {
+        let spell = spells[usize::random() % spells.len()];
+        let this = state.board[id].as_mut().unwrap();
+        this.end_goal = None;
+        this.move_target = None;
+        this.flags.set_pathing(false);
+        this.flags.set_windup(super::WindupState::Magical);
+        this.windup_time = WINDUP;
+        this.get_state_mut::<Spellbook>().casting = Some(spell);
+    } else {
+        // Get close enough to cast
+        state.board[id].as_mut().unwrap().end_goal = Some(state.player.position);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vector;
+    use crate::board::Board;
+    use crate::enemy::VTableID;
+    #[test]
+    fn caster_enemy() {
+        crate::random::seed(0);
+        let mut state = State::blank_headless();
+        state
+            .board
+            .add_enemy(Enemy::new(VTableID::Caster, Vector::new(4, 1)));
+        // Winding up then casting
+        for _ in 0..4 {
+            Board::increment(&mut state);
+        }
+        assert!(
+            EffectTracker::iter_effect_ids()
+                .any(|effect| effect.get().harmful && state.player.effect_tracker.has(effect))
+        );
+    }
+}
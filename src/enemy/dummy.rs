@@ -7,4 +7,5 @@ pub static VTABLE: VTable = VTable {
     damage: VTable::DEFAULT_DAMAGE,
     budget_cost: 0,
     tier: 9999,
+    spawn_weight: 0,
 };
@@ -0,0 +1,182 @@
+use super::Enemy;
+use super::VTable;
+use crate::Vector;
+use crate::board::Board;
+use crate::board::Noise;
+use crate::math::Direction;
+use crate::random::Random;
+use crate::state::*;
+use abes_nice_things::{FromBinary, ToBinary};
+use anyhow::Result;
+pub static VTABLE: VTable = VTable {
+    starting_health: 2,
+    is_boss: false,
+    init: VTable::default_init::<Aim>(),
+    think,
+    damage: VTable::DEFAULT_DAMAGE,
+    budget_cost: 3,
+    tier: 1,
+    spawn_weight: 4,
+};
+/// How far it can shoot
+pub const RANGE: usize = 8;
+/// It backs away if the player gets closer than this
+const PREFERRED_DISTANCE: usize = 4;
+const WINDUP: usize = 2;
+/// Where it is aiming, this is where the player was when it started winding up so that the
+/// player has a chance to dodge
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Aim(Option<Vector<usize>>);
+impl ToBinary for Aim {
+    fn to_binary(&self, binary: &mut dyn std::io::Write) -> Result<()> {
+        self.0.as_ref().to_binary(binary)
+    }
+}
+impl FromBinary for Aim {
+    fn from_binary(binary: &mut dyn std::io::Read) -> Result<Self> {
+        Ok(Aim(<Option<Vector<usize>>>::from_binary(binary)?))
+    }
+}
+fn think(state: &mut State, id: super::EnemyID) {
+    if !Enemy::try_wake(state, id) {
+        return;
+    }
+
+    let this = state.board[id].as_mut().unwrap();
+    // Are we shooting?
+    if this.flags.get_windup().is_ranged() {
+        this.windup_time -= 1;
+        if this.windup_time == 0 {
+            this.flags.set_pathing(true);
+            this.flags.set_windup(super::WindupState::None);
+            fire(state, id);
+        }
+        return;
+    }
+
+    let position = this.position;
+    let range = this.sight_range(RANGE);
+    if !Enemy::can_see_player(state, position, range) {
+        // Go find them
+        state.board[id].as_mut().unwrap().end_goal = Some(state.player.position);
+        return;
+    }
+    if position.is_near(state.player.position, PREFERRED_DISTANCE - 1) {
+        // Too close for comfort
+        let away = retreat_position(state, position);
+        let this = state.board[id].as_mut().unwrap();
+        this.end_goal = away;
+        this.move_target = away;
+        if away.is_some() {
+            return;
+        }
+        // Backed into a corner so we may as well shoot
+    }
+    // Take aim
+    let player_position = state.player.position;
+    let this = state.board[id].as_mut().unwrap();
+    this.end_goal = None;
+    this.move_target = None;
+    this.flags.set_pathing(false);
+    this.flags.set_windup(super::WindupState::Ranged);
+    this.windup_time = WINDUP;
+    this.get_state_mut::<Aim>().0 = Some(player_position);
+}
+/// Shoots at where it was aiming
+fn fire(state: &mut State, id: super::EnemyID) {
+    let this = state.board[id].as_mut().unwrap();
+    let position = this.position;
+    let Some(target) = this.get_state_mut::<Aim>().0.take() else {
+        return;
+    };
+    shoot(state, position, target, (u8::random() & 0b11) as usize + 1);
+}
+/// Shoots from the position towards the target, the shot keeps going past the target so it can
+/// still hit the player if they are further along the line. Walls and other enemies get in the
+/// way. Returns if it hit the player
+pub fn shoot(
+    state: &mut State,
+    position: Vector<usize>,
+    target: Vector<usize>,
+    damage: usize,
+) -> bool {
+    state.board.make_noise(position, Noise::COMBAT);
+    if target == position {
+        return false;
+    }
+    let (hit, _) = crate::raycast::RayCast::new(position, target)
+        .can_hit_player(true)
+        .max_range(Some(RANGE * 2))
+        .resolve(state);
+    if let Some(MapObject::Player) = hit {
+        crate::player::Player::damage(state, damage);
+        return true;
+    }
+    false
+}
+/// Picks a spot next to the position which is further from the player, if there is one
+fn retreat_position(state: &State, position: Vector<usize>) -> Option<Vector<usize>> {
+    let distance = position.abs_diff(state.player.position).sum_axes();
+    [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ]
+    .into_iter()
+    .filter(|direction| Board::enemy_can_move(state, position, *direction))
+    .map(|direction| position + direction)
+    .find(|new_position| {
+        new_position.abs_diff(state.player.position).sum_axes() > distance
+            && !state
+                .board
+                .get_possible_room_ids_at_position(*new_position)
+                .is_empty()
+    })
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enemy::VTableID;
+    #[test]
+    fn ranged_enemy() {
+        crate::random::seed(0);
+        let mut state = State::blank_headless();
+        state
+            .board
+            .add_enemy(Enemy::new(VTableID::Ranged, Vector::new(1, 6)));
+        // Winding up then shooting
+        for _ in 0..3 {
+            Board::increment(&mut state);
+        }
+        assert!(state.player.health < 50);
+
+        // Something in the way
+        let mut state = State::blank_headless();
+        state
+            .board
+            .add_enemy(Enemy::new(VTableID::Ranged, Vector::new(1, 6)));
+        state
+            .board
+            .add_enemy(Enemy::new(VTableID::Dummy, Vector::new(1, 3)));
+        for _ in 0..3 {
+            Board::increment(&mut state);
+        }
+        assert_eq!(state.player.health, 50);
+    }
+    #[test]
+    fn ranged_enemy_aim() {
+        crate::random::seed(0);
+        // It shoots where the player was when it started winding up
+        let mut state = State::blank_headless();
+        state
+            .board
+            .add_enemy(Enemy::new(VTableID::Ranged, Vector::new(1, 6)));
+        Board::increment(&mut state);
+        state.player.position = Vector::new(2, 1);
+        for _ in 0..2 {
+            Board::increment(&mut state);
+        }
+        assert_eq!(state.player.health, 50);
+    }
+}
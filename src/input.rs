@@ -1,6 +1,9 @@
+use crate::ability::AbilityID;
 use crate::math::Direction;
+use abes_nice_things::{FromBinary, ToBinary};
 use anyhow::Result;
 use std::io::Read;
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Input {
     Walk(Direction),
     MoveSelector(Direction),
@@ -8,18 +11,38 @@ pub enum Input {
     Select,
     ChangeRenderTarget,
     SkipTurn,
+    QuickSave,
+    QuickLoad,
+    /// The terminal changed size
+    Resize,
+    UseAbility(AbilityID),
+    /// Pick up what is under the player
+    PickUp,
+    /// Drop the last item picked up
+    Drop,
 }
 impl Input {
     pub fn get() -> Input {
         let mut stdin = std::io::stdin();
-        let mut buf = [0];
         loop {
-            stdin.read_exact(&mut buf).unwrap();
-            return match buf[0] {
+            // Resizing interrupts the read so we have to check before and after
+            if crate::terminal::take_resized() {
+                return Input::Resize;
+            }
+            let Some(byte) = read_byte(&mut stdin) else {
+                continue;
+            };
+            return match byte {
                 27 => {
-                    stdin.read(&mut buf).unwrap();
-                    stdin.read(&mut buf).unwrap();
-                    Input::MoveSelector(match buf[0] {
+                    // If it gets resized part way through then the rest of the sequence is
+                    // ignored as unknown keys
+                    if read_byte(&mut stdin).is_none() {
+                        continue;
+                    }
+                    let Some(byte) = read_byte(&mut stdin) else {
+                        continue;
+                    };
+                    Input::MoveSelector(match byte {
                         b'A' => Direction::Up,
                         b'B' => Direction::Down,
                         b'D' => Direction::Left,
@@ -35,44 +58,131 @@ impl Input {
                 b'\n' => Input::Select,
                 b't' => Input::ChangeRenderTarget,
                 b'\t' => Input::SkipTurn,
+                b'k' => Input::QuickSave,
+                b'l' => Input::QuickLoad,
+                b'1' => Input::UseAbility(AbilityID::Dash),
+                b'2' => Input::UseAbility(AbilityID::Shove),
+                b'3' => Input::UseAbility(AbilityID::Bolt),
+                b'g' => Input::PickUp,
+                b'f' => Input::Drop,
                 _ => continue,
             };
         }
     }
 }
 
+/// Reads a byte from stdin, going again when a signal interrupts it. If the interruption was the
+/// terminal being resized then it is None so that the resize gets handled first
+fn read_byte(stdin: &mut std::io::Stdin) -> Option<u8> {
+    let mut buf = [0];
+    loop {
+        match stdin.read(&mut buf) {
+            Ok(0) => panic!("Stdin was closed"),
+            Ok(_) => return Some(buf[0]),
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {
+                if crate::terminal::was_resized() {
+                    return None;
+                }
+            }
+            Err(error) => panic!("Failed to read input: {error}"),
+        }
+    }
+}
+
 static IS_WEIRD: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+/// Puts the terminal into raw mode so that keys come in as they are pressed
 pub fn weirdify() -> Result<()> {
     if IS_WEIRD.swap(true, std::sync::atomic::Ordering::Relaxed) {
         return Ok(());
     }
-    if std::process::Command::new("stty")
-        .arg("-echo")
-        .arg("-icanon")
-        .status()?
-        .success()
-    {
-        return Ok(());
-    }
-    Err(anyhow::Error::new(std::io::Error::new(
-        std::io::ErrorKind::Other,
-        "Failed to modify terminal, are you on windows?",
-    )))
+    crate::terminal::enable_raw_mode()
 }
+/// Puts the terminal back exactly how it was before [weirdify]
 pub fn normalize() -> Result<()> {
     if !IS_WEIRD.swap(false, std::sync::atomic::Ordering::Relaxed) {
         return Ok(());
     }
-    if std::process::Command::new("stty")
-        .arg("echo")
-        .arg("icanon")
-        .status()?
-        .success()
-    {
-        return Ok(());
+    crate::terminal::restore()
+}
+impl ToBinary for Input {
+    fn to_binary(&self, binary: &mut dyn std::io::Write) -> Result<()> {
+        match self {
+            Input::Walk(direction) => {
+                0_u8.to_binary(binary)?;
+                direction.to_binary(binary)
+            }
+            Input::MoveSelector(direction) => {
+                1_u8.to_binary(binary)?;
+                direction.to_binary(binary)
+            }
+            Input::ToggleContextMenu => 2_u8.to_binary(binary),
+            Input::Select => 3_u8.to_binary(binary),
+            Input::ChangeRenderTarget => 4_u8.to_binary(binary),
+            Input::SkipTurn => 5_u8.to_binary(binary),
+            Input::QuickSave => 6_u8.to_binary(binary),
+            Input::QuickLoad => 7_u8.to_binary(binary),
+            Input::Resize => 8_u8.to_binary(binary),
+            Input::UseAbility(ability) => {
+                9_u8.to_binary(binary)?;
+                ability.to_binary(binary)
+            }
+            Input::PickUp => 10_u8.to_binary(binary),
+            Input::Drop => 11_u8.to_binary(binary),
+        }
+    }
+}
+impl FromBinary for Input {
+    fn from_binary(binary: &mut dyn std::io::Read) -> Result<Self> {
+        Ok(match u8::from_binary(binary)? {
+            0 => Input::Walk(Direction::from_binary(binary)?),
+            1 => Input::MoveSelector(Direction::from_binary(binary)?),
+            2 => Input::ToggleContextMenu,
+            3 => Input::Select,
+            4 => Input::ChangeRenderTarget,
+            5 => Input::SkipTurn,
+            6 => Input::QuickSave,
+            7 => Input::QuickLoad,
+            8 => Input::Resize,
+            9 => Input::UseAbility(AbilityID::from_binary(binary)?),
+            10 => Input::PickUp,
+            11 => Input::Drop,
+            _ => {
+                return Err(anyhow::Error::new(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Could not get Input from binary due to invalid discriminant",
+                )));
+            }
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    #[test]
+    fn input_binary() {
+        let tests = [
+            Input::Walk(Direction::Up),
+            Input::Walk(Direction::Left),
+            Input::MoveSelector(Direction::Down),
+            Input::MoveSelector(Direction::Right),
+            Input::ToggleContextMenu,
+            Input::Select,
+            Input::ChangeRenderTarget,
+            Input::SkipTurn,
+            Input::QuickSave,
+            Input::QuickLoad,
+            Input::Resize,
+            Input::UseAbility(AbilityID::Dash),
+            Input::UseAbility(AbilityID::Bolt),
+            Input::PickUp,
+            Input::Drop,
+        ];
+        let mut buf = VecDeque::new();
+        for test in tests.into_iter() {
+            test.to_binary(&mut buf).unwrap();
+            assert_eq!(test, Input::from_binary(&mut buf).unwrap());
+        }
+        assert_eq!(buf.len(), 0);
     }
-    Err(anyhow::Error::new(std::io::Error::new(
-        std::io::ErrorKind::Other,
-        "Failed to reset terminal, how did you get this far?",
-    )))
 }
@@ -0,0 +1,377 @@
+use crate::effect::EffectID;
+use crate::effect::EffectTracker;
+use crate::player::Player;
+use crate::state::Entity;
+use crate::state::State;
+use abes_nice_things::{FromBinary, ToBinary};
+use anyhow::Result;
+
+/// Something which can lie on the board or be carried by the player
+#[derive(Clone, Copy, Debug)]
+pub struct Item {
+    pub name: &'static str,
+    /// What it is drawn as while it is lying on the board
+    pub symbol: char,
+    /// Where it goes when equipped and what it changes, None if it can't be equipped
+    pub equipment: Option<Equipment>,
+    /// What happens when it is used, returns if it got used up. None if it can't be used
+    pub on_use: Option<fn(&mut State) -> bool>,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Slot {
+    Weapon,
+    Armor,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Equipment {
+    pub slot: Slot,
+    /// Weapons replace the damage and reach of the player, armor only adds defense
+    pub stats: Stats,
+}
+/// How well the player fights
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Stats {
+    /// The damage of an attack is somewhere between min and max, inclusive
+    pub min_damage: usize,
+    pub max_damage: usize,
+    /// How far away the player can attack from with the selector
+    pub reach: usize,
+    /// How much incoming damage is reduced by, a hit always does at least 1 damage
+    pub defense: usize,
+}
+impl Stats {
+    /// What the player has with nothing equipped
+    pub const UNARMED: Stats = Stats {
+        min_damage: 1,
+        max_damage: 1,
+        reach: 1,
+        defense: 0,
+    };
+    const fn weapon(min_damage: usize, max_damage: usize, reach: usize) -> Stats {
+        Stats {
+            min_damage,
+            max_damage,
+            reach,
+            defense: 0,
+        }
+    }
+    const fn armor(defense: usize) -> Stats {
+        Stats {
+            defense,
+            ..Stats::UNARMED
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum ItemID {
+    HealthPotion = 0,
+    EnergyTonic = 1,
+    Antidote = 2,
+    Dagger = 3,
+    Sword = 4,
+    Spear = 5,
+    LeatherArmor = 6,
+    PlateArmor = 7,
+}
+impl ToBinary for ItemID {
+    fn to_binary(&self, binary: &mut dyn std::io::prelude::Write) -> Result<()> {
+        self.to_raw().to_binary(binary)
+    }
+}
+impl FromBinary for ItemID {
+    fn from_binary(binary: &mut dyn std::io::prelude::Read) -> Result<Self> {
+        let raw = u8::from_binary(binary)?;
+        if raw >= ITEMS.len() as u8 {
+            anyhow::bail!("Invalid item id {raw}");
+        }
+        Ok(ItemID::from_raw(raw))
+    }
+}
+impl ItemID {
+    pub fn from_raw(raw: u8) -> ItemID {
+        if raw >= ITEMS.len() as u8 {
+            panic!("Tried to make invalid ItemID: ({raw})")
+        }
+        unsafe { std::mem::transmute(raw) }
+    }
+    fn to_raw(self) -> u8 {
+        unsafe { std::mem::transmute(self) }
+    }
+    pub fn get(self) -> &'static Item {
+        &ITEMS[self.to_raw() as usize]
+    }
+}
+/// The most items the player can carry at once
+pub const INVENTORY_SIZE: usize = 10;
+/// Picks up the item the player is standing on, returns if the turn should be incremented
+pub fn pick_up(state: &mut State) -> bool {
+    if state.player.inventory.len() >= INVENTORY_SIZE {
+        state.feedback = "You can't carry any more".to_string();
+        return false;
+    }
+    let Some(id) = state.board.take_item_at(state.player.position) else {
+        state.feedback = "There is nothing here".to_string();
+        return false;
+    };
+    state.player.inventory.push(id);
+    state.feedback = format!("Picked up {}", id.get().name);
+    true
+}
+/// Drops the item in the slot where the player is standing, returns if the turn should be
+/// incremented
+pub fn drop(state: &mut State, slot: usize) -> bool {
+    if slot >= state.player.inventory.len() {
+        state.feedback = "There is nothing to drop".to_string();
+        return false;
+    }
+    let id = state.player.inventory.remove(slot);
+    state.board.add_item(state.player.position, id);
+    state.feedback = format!("Dropped {}", id.get().name);
+    true
+}
+/// Uses the item in the slot, removing it if it got used up. Returns if the turn should be
+/// incremented
+pub fn use_item(state: &mut State, slot: usize) -> bool {
+    let Some(id) = state.player.inventory.get(slot).copied() else {
+        return false;
+    };
+    let Some(on_use) = id.get().on_use else {
+        state.feedback = format!("{} can't be used", id.get().name);
+        return false;
+    };
+    if !(on_use)(state) {
+        return false;
+    }
+    state.player.inventory.remove(slot);
+    true
+}
+/// Puts on the item in the inventory slot, whatever was in its equipment slot goes back into the
+/// inventory. Returns if the turn should be incremented
+pub fn equip(state: &mut State, slot: usize) -> bool {
+    let Some(id) = state.player.inventory.get(slot).copied() else {
+        return false;
+    };
+    let Some(equipment) = id.get().equipment else {
+        state.feedback = format!("{} can't be equipped", id.get().name);
+        return false;
+    };
+    state.player.inventory.remove(slot);
+    if let Some(previous) = state.player.get_equipped_mut(equipment.slot).replace(id) {
+        state.player.inventory.push(previous);
+    }
+    state.feedback = format!("Equipped {}", id.get().name);
+    true
+}
+/// Takes off whatever is in the equipment slot, returns if the turn should be incremented
+pub fn unequip(state: &mut State, slot: Slot) -> bool {
+    if state.player.inventory.len() >= INVENTORY_SIZE {
+        state.feedback = "You can't carry any more".to_string();
+        return false;
+    }
+    let Some(id) = state.player.get_equipped_mut(slot).take() else {
+        return false;
+    };
+    state.player.inventory.push(id);
+    state.feedback = format!("Unequipped {}", id.get().name);
+    true
+}
+const POTION_HEALING: usize = 25;
+pub static ITEMS: &[Item] = &[
+    Item {
+        name: "Health potion",
+        symbol: '!',
+        equipment: None,
+        on_use: Some(|state| {
+            if state.player.health >= state.player.max_health {
+                state.feedback = "You are already at full health".to_string();
+                return false;
+            }
+            Player::heal(state, POTION_HEALING);
+            state.feedback = "You feel better".to_string();
+            true
+        }),
+    },
+    Item {
+        name: "Energy tonic",
+        symbol: '~',
+        equipment: None,
+        on_use: Some(|state| {
+            if state.player.energy >= state.player.max_energy {
+                state.feedback = "You are already full of energy".to_string();
+                return false;
+            }
+            state.player.energy = state.player.max_energy;
+            state.feedback = "You feel energized".to_string();
+            true
+        }),
+    },
+    Item {
+        name: "Antidote",
+        symbol: '+',
+        equipment: None,
+        // Cures whatever is doing damage over time
+        on_use: Some(|state| {
+            let cures = [EffectID::Poison, EffectID::Bleed];
+            if !cures
+                .iter()
+                .any(|effect| state.player.effect_tracker.has(*effect))
+            {
+                state.feedback = "There is nothing to cure".to_string();
+                return false;
+            }
+            for effect in cures {
+                EffectTracker::clear(state, Entity::Player, effect);
+            }
+            state.feedback = "You were cured".to_string();
+            true
+        }),
+    },
+    Item {
+        name: "Dagger",
+        symbol: '/',
+        equipment: Some(Equipment {
+            slot: Slot::Weapon,
+            stats: Stats::weapon(1, 2, 1),
+        }),
+        on_use: None,
+    },
+    Item {
+        name: "Sword",
+        symbol: '/',
+        equipment: Some(Equipment {
+            slot: Slot::Weapon,
+            stats: Stats::weapon(2, 3, 1),
+        }),
+        on_use: None,
+    },
+    Item {
+        name: "Spear",
+        symbol: '/',
+        equipment: Some(Equipment {
+            slot: Slot::Weapon,
+            stats: Stats::weapon(1, 3, 2),
+        }),
+        on_use: None,
+    },
+    Item {
+        name: "Leather armor",
+        symbol: '[',
+        equipment: Some(Equipment {
+            slot: Slot::Armor,
+            stats: Stats::armor(1),
+        }),
+        on_use: None,
+    },
+    Item {
+        name: "Plate armor",
+        symbol: '[',
+        equipment: Some(Equipment {
+            slot: Slot::Armor,
+            stats: Stats::armor(2),
+        }),
+        on_use: None,
+    },
+];
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::board::tile::Tile;
+    use crate::enemy::{Enemy, VTableID};
+    use crate::input::Input;
+    use crate::math::Vector;
+    use std::collections::VecDeque;
+    #[test]
+    fn items() {
+        let mut state = State::blank_headless();
+        let position = state.player.position;
+        state.board.add_item(position, ItemID::HealthPotion);
+        state.simulate([Input::PickUp]);
+        assert_eq!(state.player.inventory, vec![ItemID::HealthPotion]);
+        assert_eq!(state.board.get_items_at(position).count(), 0);
+        // Nothing left so no turn is taken
+        state.simulate([Input::PickUp]);
+        assert_eq!(state.total_turns, 1);
+
+        // Used up items are removed
+        assert!(use_item(&mut state, 0));
+        assert_eq!(state.player.health, 75);
+        assert!(state.player.inventory.is_empty());
+
+        state.player.inventory.push(ItemID::Antidote);
+        // Antidotes aren't wasted when there is nothing to cure
+        assert!(!use_item(&mut state, 0));
+        state.simulate([Input::Drop]);
+        assert!(state.player.inventory.is_empty());
+        assert_eq!(
+            state.board.get_items_at(position).collect::<Vec<_>>(),
+            vec![ItemID::Antidote]
+        );
+
+        let mut buf = VecDeque::new();
+        state.board.to_binary(&mut buf).unwrap();
+        let board = Board::from_binary(&mut buf).unwrap();
+        assert_eq!(board.get_items_at(position).count(), 1);
+        assert_eq!(buf.len(), 0);
+
+        // Being stunned stops items being used from the menu
+        state.player.inventory.push(ItemID::EnergyTonic);
+        state.player.energy = 0;
+        EffectTracker::apply(&mut state, Entity::Player, EffectID::Stun, None);
+        assert!(!state.act_from_menu(|state| use_item(state, 0)));
+        assert_eq!(state.player.inventory, vec![ItemID::EnergyTonic]);
+    }
+    #[test]
+    fn equipment() {
+        let mut state = State::blank_headless();
+        state.player.inventory = vec![ItemID::Spear, ItemID::PlateArmor, ItemID::Sword];
+        assert!(equip(&mut state, 0));
+        assert!(equip(&mut state, 0));
+        assert_eq!(state.player.weapon, Some(ItemID::Spear));
+        assert_eq!(state.player.armor, Some(ItemID::PlateArmor));
+        let stats = state.player.get_stats();
+        assert_eq!((stats.min_damage, stats.max_damage), (1, 3));
+        assert_eq!(stats.reach, 2);
+        assert_eq!(stats.defense, 2);
+
+        // Swapping puts the old one back
+        assert!(equip(&mut state, 0));
+        assert_eq!(state.player.weapon, Some(ItemID::Sword));
+        assert_eq!(state.player.inventory, vec![ItemID::Spear]);
+        // Potions aren't gear
+        state.player.inventory.push(ItemID::HealthPotion);
+        assert!(!equip(&mut state, 1));
+
+        // Armor takes the edge off but hits always land
+        Player::damage(&mut state, 5);
+        assert_eq!(state.player.health, 47);
+        Player::damage(&mut state, 1);
+        assert_eq!(state.player.health, 46);
+
+        let mut buf = VecDeque::new();
+        state.player.to_binary(&mut buf).unwrap();
+        let player = Player::from_binary(&mut buf).unwrap();
+        assert_eq!(player.weapon, Some(ItemID::Sword));
+        assert_eq!(player.armor, Some(ItemID::PlateArmor));
+        assert_eq!(buf.len(), 0);
+
+        // There has to be room to take it off
+        state.player.inventory = vec![ItemID::HealthPotion; INVENTORY_SIZE];
+        assert!(!unequip(&mut state, Slot::Armor));
+        state.player.inventory.pop();
+        assert!(unequip(&mut state, Slot::Armor));
+        assert_eq!(state.player.get_stats().defense, 0);
+
+        // Reach doesn't go through walls
+        state.player.weapon = Some(ItemID::Spear);
+        state
+            .board
+            .add_enemy(Enemy::new(VTableID::Dummy, Vector::new(1, 3)));
+        state.board[Vector::new(1, 2)] = Some(Tile::Wall);
+        state.player.selector = Vector::new(1, 3);
+        assert!(!state.handle_select_input());
+        state.board[Vector::new(1, 2)] = None;
+        assert!(state.handle_select_input());
+    }
+}
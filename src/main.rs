@@ -1,19 +1,26 @@
 // Modules
+mod ability;
+mod args;
 mod board;
 mod context_menu;
+mod dungeon;
 mod effect;
 mod enemy;
 mod input;
+mod item;
 mod math;
 mod player;
 mod random;
 mod raycast;
+mod replay;
+mod save;
+mod screen;
 mod state;
+mod terminal;
 
 use std::io::Write;
 
-use board::AxisLength;
-use input::Input;
+use anyhow::{Context, Result};
 use input::normalize;
 use input::weirdify;
 use math::Vector;
@@ -32,127 +39,156 @@ use math::Zone;
 
 fn main() {
     abes_nice_things::set_log_path("log").expect("Failed to set log path");
-    if let Err(error) = std::panic::catch_unwind(run) {
-        // Panic handling
-        let _ = normalize();
-        print!("\x1b(B"); // reset confusion
-        let _ = std::io::stdout().flush();
-
-        std::panic::panic_any(error)
+    let args = match args::Args::parse() {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{error}\n\n{}", args::HELP);
+            std::process::exit(1)
+        }
+    };
+    if args.help {
+        println!("{}", args::HELP);
+        return;
     }
-    // Just because it didn't error doesn't mean we don't want to clean up
-    else {
-        normalize().unwrap();
-        print!("\x1b[(B");
-        std::io::stdout().flush().unwrap();
+    match std::panic::catch_unwind(|| run(args)) {
+        Err(error) => {
+            // Panic handling
+            let _ = normalize();
+            print!("\x1b(B"); // reset confusion
+            let _ = std::io::stdout().flush();
+
+            std::panic::panic_any(error)
+        }
+        // Just because it didn't error doesn't mean we don't want to clean up
+        Ok(result) => {
+            normalize().unwrap();
+            print!("\x1b[(B");
+            std::io::stdout().flush().unwrap();
+            if let Err(error) = result {
+                eprintln!("{error:?}");
+                std::process::exit(1)
+            }
+        }
     }
 }
-fn run() {
-    let terminal_size = get_terminal_size();
-    let mut state = state::State::new(
-        board::map_gen::generate(
-            AxisLength::Full,
-            calc_desired_dimensions(terminal_size),
-            10000,
+fn run(args: args::Args) -> Result<()> {
+    let terminal_size = terminal::get_size()
+        .context("Could not get the size of the terminal, is it a terminal?")?;
+    let mut state = if let Some(path) = &args.replay {
+        let (mut state, replay) =
+            replay::Replay::load(path, std::time::Duration::from_millis(args.replay_delay))?;
+        state.replay = Some(replay);
+        state.finish_load();
+        state
+    } else if let Some(path) = &args.load {
+        let mut state = save::read(
+            &mut std::fs::File::open(path).with_context(|| format!("Failed to open {path}"))?,
         )
-        .unwrap(),
-        player::Player::new(Vector::new(1, 1)),
-        terminal_size,
-    );
+        .with_context(|| format!("Failed to load {path}"))?;
+        state.finish_load();
+        state
+    } else {
+        new_run(
+            &args,
+            args.seed.unwrap_or_else(random::generate_seed),
+            terminal_size,
+        )?
+    };
+    state.autosave_interval = Some(args.autosave).filter(|interval| *interval != 0);
+    if let Some(path) = &args.record {
+        state.start_recording(path)?;
+    }
 
-    weirdify().unwrap();
+    terminal::watch_resizes();
+    weirdify()?;
     loop {
         state.render();
-        if match Input::get() {
-            Input::Walk(direction) => player::Player::handle_walk_input(&mut state, direction),
-            Input::MoveSelector(direction) => state.handle_move_selector_input(direction),
-            Input::ChangeRenderTarget => {
-                player::Player::handle_change_render_target_input(&mut state);
-                false
-            }
-            Input::ToggleContextMenu => state.handle_toggle_context_menu_input(),
-            Input::Select => {
-                state.handle_select_input()
-                /*if state.board.count_enemies() == 0 {
-                    state.board.add_enemy(enemy::Enemy::new(
-                        &enemy::dummy::VTABLE,
-                        state.player.selector,
-                    ));
-                } else {
-                    state
-                        .board
-                        .get_enemy_mut(board::EnemyID(0))
-                        .as_mut()
-                        .unwrap()
-                        .move_target = Some(state.player.selector);
-                }*/
+        if state.player.is_dead() {
+            match state.game_over() {
+                state::GameOver::Restart => {
+                    // The new run gets recorded, so replays start it from the same seed
+                    let screen_size = state.screen_size;
+                    state.load(|| new_run(&args, random::generate_seed(), screen_size))?;
+                    state.feedback = format!("Started a new run with seed {}", state.seed);
+                }
+                state::GameOver::Reloaded => {}
+                state::GameOver::Quit => return Ok(()),
             }
-            Input::SkipTurn => true,
-        } {
-            state.increment();
+            continue;
         }
+        let input = state.next_input();
+        state.handle_input(input);
     }
 }
+/// Generates a new board from the seed and puts the player at the start of it
+fn new_run(args: &args::Args, seed: u64, terminal_size: Vector<usize>) -> Result<state::State> {
+    random::seed(seed);
+    Ok(state::State::new(
+        board::map_gen::generate(
+            args.axis_length,
+            calc_desired_dimensions(terminal_size).unwrap_or(MINIMUM_VIEWPORT),
+            args.budget,
+        )?,
+        player::Player::new(Vector::new(1, 1)),
+        terminal_size,
+        seed,
+        args.budget,
+    ))
+}
+/// The smallest the viewport can be, if the terminal can't fit it then the game waits for the
+/// terminal to be enlarged
+const MINIMUM_VIEWPORT: Vector<usize> = Vector::new(20, 10);
+/// The rows needed under the viewport for the bars/meta ui:
+///  border
+///  feedback
+///  health
+///  energy
+///  meta info
+///  equipment
+///  input
+const ROWS_UNDER_VIEWPORT: usize = 7;
 /// Calculates the desired width, height for the viewport. It gets the terminal's size then
-/// subtracts the areas needed for other parts of the ui. If the resulting viewport would be too
-/// small then it panics.
+/// subtracts the areas needed for other parts of the ui. If the resulting viewport would be
+/// smaller than [MINIMUM_VIEWPORT] then it is None.
 ///
 /// When using this to create a [Zone] for the viewport, remember to subtract 1 from the width and
 /// height first because [Zone]s are inclusive.
-fn calc_desired_dimensions(mut screen_size: Vector<usize>) -> Vector<usize> {
-    // Viewport border
-    screen_size -= 1;
-
-    // bars/meta ui:
-    //  feedback
-    //  health
-    //  energy
-    //  meta info
-    //  input
-    screen_size.y -= 5;
-
-    // Right column
-    screen_size.x -= context_menu::COLUMNS_NEEDED;
+fn calc_desired_dimensions(screen_size: Vector<usize>) -> Option<Vector<usize>> {
+    // Viewport border and right column
+    let width = screen_size
+        .x
+        .checked_sub(1 + context_menu::COLUMNS_NEEDED)?;
+    let height = screen_size.y.checked_sub(ROWS_UNDER_VIEWPORT)?;
 
     // validity checks
-    if screen_size.x < 20 {
-        panic!("Terminal is under minimum width")
-    }
-    if screen_size.y < 10 {
-        panic!("terminal is under minimum height")
+    if width < MINIMUM_VIEWPORT.x || height < MINIMUM_VIEWPORT.y {
+        return None;
     }
-    screen_size
+    Some(Vector::new(width, height))
 }
-/// Gets the size of the terminal in width, height.
-///
-/// This takes about 10ms independant of whether it is release or debug.
-fn get_terminal_size() -> Vector<usize> {
-    // These get the width and height respectively, the reason why they have to inherit stderr is
-    // because they ask stderr what size it is
+/// Gets the smallest terminal which can fit the minimum viewport
+fn minimum_screen_size() -> Vector<usize> {
     Vector::new(
-        String::from_utf8(
-            std::process::Command::new("tput")
-                .arg("cols")
-                .stderr(std::process::Stdio::inherit())
-                .output()
-                .unwrap()
-                .stdout,
-        )
-        .unwrap()
-        .trim()
-        .parse()
-        .expect("This NEEDS stderr to be the terminal in order to work"),
-        String::from_utf8(
-            std::process::Command::new("tput")
-                .arg("lines")
-                .stderr(std::process::Stdio::inherit())
-                .output()
-                .unwrap()
-                .stdout,
-        )
-        .unwrap()
-        .trim()
-        .parse()
-        .expect("This NEEDS stderr to be the terminal in order to work"),
+        MINIMUM_VIEWPORT.x + 1 + context_menu::COLUMNS_NEEDED,
+        MINIMUM_VIEWPORT.y + ROWS_UNDER_VIEWPORT,
     )
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn desired_dimensions() {
+        // Exactly big enough gives the minimum viewport
+        let minimum = minimum_screen_size();
+        assert_eq!(calc_desired_dimensions(minimum), Some(MINIMUM_VIEWPORT));
+        // Any smaller doesn't fit
+        assert_eq!(calc_desired_dimensions(minimum - Vector::new(1, 0)), None);
+        assert_eq!(calc_desired_dimensions(minimum - Vector::new(0, 1)), None);
+        assert_eq!(calc_desired_dimensions(Vector::new(0, 0)), None);
+        // Everything past the minimum goes to the viewport
+        assert_eq!(
+            calc_desired_dimensions(minimum + Vector::new(10, 5)),
+            Some(MINIMUM_VIEWPORT + Vector::new(10, 5))
+        );
+    }
+}
@@ -1,6 +1,10 @@
+use crate::item::Slot;
+use crate::item::Stats;
 use crate::math::Direction;
 use crate::math::Vector;
 use crate::math::Zone;
+use crate::random::Random;
+use crate::screen::Screen;
 use crate::state::State;
 use abes_nice_things::Style;
 use abes_nice_things::{FromBinary, ToBinary};
@@ -17,6 +21,12 @@ pub struct Player {
     pub max_energy: usize,
     pub no_interact_range_limit: bool,
     pub effect_tracker: crate::effect::EffectTracker,
+    /// While hasted every other action is free, this is if the next one is
+    pub free_action: bool,
+    /// What the player is carrying, in the order it was picked up
+    pub inventory: Vec<crate::item::ItemID>,
+    pub weapon: Option<crate::item::ItemID>,
+    pub armor: Option<crate::item::ItemID>,
 }
 impl ToBinary for Player {
     fn to_binary(&self, binary: &mut dyn Write) -> Result<()> {
@@ -28,7 +38,29 @@ impl ToBinary for Player {
         self.energy.to_binary(binary)?;
         self.max_energy.to_binary(binary)?;
         self.no_interact_range_limit.to_binary(binary)?;
-        self.effect_tracker.to_binary(binary)
+        self.effect_tracker.to_binary(binary)?;
+        self.free_action.to_binary(binary)?;
+        self.inventory.to_binary(binary)?;
+        self.weapon.as_ref().to_binary(binary)?;
+        self.armor.as_ref().to_binary(binary)
+    }
+}
+#[cfg(test)]
+impl Player {
+    /// Writes the player how it was before saves had versions, confusion was the only effect
+    pub fn write_version_0(&self, binary: &mut dyn Write) -> Result<()> {
+        self.position.to_binary(binary)?;
+        self.selector.to_binary(binary)?;
+        self.render_target.to_binary(binary)?;
+        self.health.to_binary(binary)?;
+        self.max_health.to_binary(binary)?;
+        self.energy.to_binary(binary)?;
+        self.max_energy.to_binary(binary)?;
+        self.no_interact_range_limit.to_binary(binary)?;
+        self.effect_tracker
+            .get(crate::effect::EffectID::Confusion)
+            .as_ref()
+            .to_binary(binary)
     }
 }
 impl FromBinary for Player {
@@ -43,6 +75,29 @@ impl FromBinary for Player {
             max_energy: usize::from_binary(binary)?,
             no_interact_range_limit: bool::from_binary(binary)?,
             effect_tracker: crate::effect::EffectTracker::from_binary(binary)?,
+            // Before version 6 there was no haste
+            free_action: if crate::save::loading_version() >= 6 {
+                bool::from_binary(binary)?
+            } else {
+                false
+            },
+            // Before version 9 there were no items
+            inventory: if crate::save::loading_version() >= 9 {
+                <Vec<crate::item::ItemID>>::from_binary(binary)?
+            } else {
+                Vec::new()
+            },
+            // Before version 10 nothing could be equipped
+            weapon: if crate::save::loading_version() >= 10 {
+                <Option<crate::item::ItemID>>::from_binary(binary)?
+            } else {
+                None
+            },
+            armor: if crate::save::loading_version() >= 10 {
+                <Option<crate::item::ItemID>>::from_binary(binary)?
+            } else {
+                None
+            },
         })
     }
 }
@@ -58,17 +113,15 @@ impl Player {
             max_energy: 5,
             no_interact_range_limit: false,
             effect_tracker: crate::effect::EffectTracker::default(),
+            free_action: false,
+            inventory: Vec::new(),
+            weapon: None,
+            armor: None,
         }
     }
-    pub fn position_cursor(&self, viewport: Zone<usize>, buffer: &mut impl Write) {
-        let visual_position = viewport.clamp(self.selector) - viewport.top_left();
-        write!(
-            buffer,
-            "\x1b[{};{}H",
-            visual_position.y + 1,
-            visual_position.x + 1
-        )
-        .unwrap();
+    /// Where the cursor should be left on the screen, which is the selector
+    pub fn get_cursor_position(&self, viewport: Zone<usize>) -> Vector<usize> {
+        viewport.clamp(self.selector) - viewport.top_left()
     }
     pub fn get_render_target_pos(&self) -> Vector<usize> {
         match self.render_target {
@@ -86,11 +139,17 @@ impl Player {
             {
                 Player::attack(state, id);
                 return true;
+            } else if state.board.is_door_locked(state.player.position + move_dir) {
+                state.feedback = "The door is sealed".to_string();
+                return false;
             } else if let Some(crate::board::tile::Tile::Door { open, .. }) =
                 &mut state.board[state.player.position + move_dir]
                 && !*open
             {
                 *open = true;
+                state
+                    .board
+                    .make_noise(state.player.position + move_dir, crate::board::Noise::DOOR);
                 return true;
             }
             return false;
@@ -100,7 +159,46 @@ impl Player {
         true
     }
     pub fn attack(state: &mut State, target: crate::board::EnemyID) {
-        (state.board[target].as_ref().unwrap().get_vtable().damage)(state, target, 1);
+        state
+            .board
+            .make_noise(state.player.position, crate::board::Noise::COMBAT);
+        let stats = state.player.get_stats();
+        // Only rolling when there is a range keeps fixed damage from using up randomness
+        let damage = if stats.max_damage > stats.min_damage {
+            stats.min_damage + usize::random() % (stats.max_damage - stats.min_damage + 1)
+        } else {
+            stats.min_damage
+        };
+        if (state.board[target].as_ref().unwrap().get_vtable().damage)(state, target, damage) {
+            state.stats.kills += 1;
+        }
+    }
+    /// What the player fights with, weapons replace the unarmed damage and reach and armor adds
+    /// defense
+    pub fn get_stats(&self) -> Stats {
+        let mut stats = Stats::UNARMED;
+        if let Some(equipment) = self.weapon.and_then(|weapon| weapon.get().equipment) {
+            stats.min_damage = equipment.stats.min_damage;
+            stats.max_damage = equipment.stats.max_damage;
+            stats.reach = equipment.stats.reach;
+            stats.defense += equipment.stats.defense;
+        }
+        if let Some(equipment) = self.armor.and_then(|armor| armor.get().equipment) {
+            stats.defense += equipment.stats.defense;
+        }
+        stats
+    }
+    pub fn get_equipped(&self, slot: Slot) -> Option<crate::item::ItemID> {
+        match slot {
+            Slot::Weapon => self.weapon,
+            Slot::Armor => self.armor,
+        }
+    }
+    pub fn get_equipped_mut(&mut self, slot: Slot) -> &mut Option<crate::item::ItemID> {
+        match slot {
+            Slot::Weapon => &mut self.weapon,
+            Slot::Armor => &mut self.armor,
+        }
     }
     pub fn handle_move_selector_input(state: &mut State, direction: Direction) {
         let viewport = state
@@ -122,24 +220,40 @@ impl Player {
             RenderTarget::Selector => RenderTarget::Player,
         };
     }
-    pub fn render(&self, viewport: Zone<usize>, buffer: &mut impl Write) {
+    pub fn render(&self, viewport: Zone<usize>, screen: &mut Screen) {
         // Only draw the player if we can see the player
         if viewport.contains(self.position) {
-            let visual_pos = self.position - viewport.top_left();
-            write!(
-                buffer,
-                "\x1b[{};{}H{}@\x1b[0m",
-                visual_pos.y + 1,
-                visual_pos.x + 1,
-                Style::new().cyan().intense(true)
-            )
-            .unwrap();
+            screen.set(
+                self.position - viewport.top_left(),
+                '@',
+                Some(*Style::new().cyan().intense(true)),
+            );
         }
     }
     pub fn damage(state: &mut State, damage: usize) {
-        state.player.health = state.player.health.saturating_sub(damage);
+        // Armor softens hits but can't stop them completely
+        let damage = if damage == 0 {
+            0
+        } else {
+            damage
+                .saturating_sub(state.player.get_stats().defense)
+                .max(1)
+        };
+        let taken = damage.min(state.player.health);
+        state.player.health -= taken;
+        state.stats.damage_taken += taken;
+    }
+    pub fn is_dead(&self) -> bool {
+        self.health == 0
+    }
+    pub fn heal(state: &mut State, amount: usize) {
+        state.player.health = (state.player.health + amount).min(state.player.max_health);
     }
     pub fn increment(state: &mut State) {
+        if state.total_turns % crate::ability::ENERGY_REGEN_INTERVAL == 0 {
+            state.player.energy = (state.player.energy + 1).min(state.player.max_energy);
+        }
+        crate::effect::EffectTracker::run_on_turns(state, crate::state::Entity::Player);
         let finished = state.player.effect_tracker.decriment();
         crate::effect::EffectTracker::run_on_ends(state, crate::state::Entity::Player, finished);
     }
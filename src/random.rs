@@ -1,8 +1,80 @@
 use crate::math::*;
-use std::io::Read;
+use abes_nice_things::{FromBinary, ToBinary};
+use anyhow::Result;
+use std::hash::BuildHasher;
 thread_local! {
-    static URANDOM: std::cell::RefCell<std::io::BufReader<std::fs::File>> =
-        std::cell::RefCell::new(std::io::BufReader::new(std::fs::File::open("/dev/urandom").unwrap()));
+    static RNG: std::cell::RefCell<Rng> = std::cell::RefCell::new(Rng::from_seed(generate_seed()));
+}
+/// The generator behind everything random, it is xoshiro256** seeded through splitmix64.
+///
+/// There is one per thread and it is seeded from the system on first use, if you want a
+/// reproducible run then use [seed] before generating anything.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rng {
+    state: [u64; 4],
+}
+impl Rng {
+    pub fn from_seed(mut seed: u64) -> Rng {
+        // Expanding the seed with splitmix so that similar seeds don't give similar sequences and
+        // so that the state can never be all zeros
+        let mut state = [0; 4];
+        for part in state.iter_mut() {
+            seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            *part = z ^ (z >> 31);
+        }
+        Rng { state }
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        let out = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let shifted = self.state[1] << 17;
+
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+
+        self.state[2] ^= shifted;
+        self.state[3] = self.state[3].rotate_left(45);
+
+        out
+    }
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+impl ToBinary for Rng {
+    fn to_binary(&self, binary: &mut dyn std::io::prelude::Write) -> Result<()> {
+        self.state.to_binary(binary)
+    }
+}
+impl FromBinary for Rng {
+    fn from_binary(binary: &mut dyn std::io::prelude::Read) -> Result<Self> {
+        Ok(Rng {
+            state: <[u64; 4]>::from_binary(binary)?,
+        })
+    }
+}
+/// Resets the generator so that everything random from now on is decided by the seed
+pub fn seed(seed: u64) {
+    RNG.with_borrow_mut(|rng| *rng = Rng::from_seed(seed))
+}
+/// Gets a seed from the system, this is the only source of randomness which is not reproducible
+pub fn generate_seed() -> u64 {
+    std::collections::hash_map::RandomState::new().hash_one(std::time::SystemTime::now())
+}
+/// Gets the current state of the generator so that it can be saved and continued later
+pub fn get_state() -> Rng {
+    RNG.with_borrow(|rng| *rng)
+}
+/// Continues from a previously saved state of the generator
+pub fn set_state(state: Rng) {
+    RNG.with_borrow_mut(|rng| *rng = state)
 }
 /// Creates a uniform random number between 1 and 2
 ///
@@ -29,16 +101,16 @@ pub trait Random {
 }
 impl Random for bool {
     fn random() -> Self {
-        unsafe { std::mem::transmute(u8::random()) }
+        (u8::random() & 0b1) != 0
     }
 }
 macro_rules! random_int_helper {
     ($type:ty) => {
         impl Random for $type {
             fn random() -> Self {
-                URANDOM.with_borrow_mut(|urandom| {
+                RNG.with_borrow_mut(|rng| {
                     let mut buf = [0; std::mem::size_of::<$type>()];
-                    urandom.read_exact(&mut buf).unwrap();
+                    rng.fill_bytes(&mut buf);
                     <$type>::from_le_bytes(buf)
                 })
             }
@@ -82,3 +154,35 @@ impl PickRandom for Zone<usize> {
         )
     }
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    #[test]
+    fn same_seed_same_sequence() {
+        seed(1234);
+        let first: Vec<u64> = (0..1000).map(|_| u64::random()).collect();
+        seed(1234);
+        let second: Vec<u64> = (0..1000).map(|_| u64::random()).collect();
+        assert_eq!(first, second);
+    }
+    #[test]
+    fn state_continues() {
+        seed(4321);
+        let state = get_state();
+        let first: Vec<u32> = (0..1000).map(|_| u32::random()).collect();
+        set_state(state);
+        let second: Vec<u32> = (0..1000).map(|_| u32::random()).collect();
+        assert_eq!(first, second);
+    }
+    #[test]
+    fn rng_binary() {
+        let mut buf = VecDeque::new();
+        for _ in 0..1000 {
+            let test = Rng::from_seed(u64::random());
+            test.to_binary(&mut buf).unwrap();
+            assert_eq!(test, Rng::from_binary(&mut buf).unwrap());
+        }
+        assert_eq!(buf.len(), 0);
+    }
+}
@@ -96,8 +96,10 @@ impl RayCast {
                 logical_target - (position.prim_as() + 0.5)
             };
             // Check stop conditions
+            // Whatever is at the start is what cast it so it can't be hit
+            let at_start = position == self.start;
             // Hitting a player
-            if self.can_hit_player && position == state.player.position {
+            if self.can_hit_player && !at_start && position == state.player.position {
                 return (Some(MapObject::Player), path);
             }
             // Hitting a tile
@@ -108,6 +110,7 @@ impl RayCast {
             }
             // Hitting an enemy
             if self.can_hit_enemy
+                && !at_start
                 && let Some(enemy) = state.board.get_enemy_at_position(position)
             {
                 return (Some(MapObject::Enemy(enemy)), path);
@@ -123,28 +126,23 @@ impl RayCast {
                 assert_eq!(steps_taken, self.start.abs_diff(self.target).sum_axes());
                 return (None, path);
             }
-            let mut x_style = abes_nice_things::Style::new();
-            let mut y_style = x_style.clone();
             // Figuring out which direction we need to go next
             // figuring out possible next positions
             let diff_x = logical_diff.x; // pure sugar
             let next_target_x = match diff_x {
                 // right is positive x
                 _right if diff_x > 0_f64 => {
-                    x_style.background_green();
                     // move towards the next integer away from 0
                     (logical_position.x + 1_f64).floor()
                 }
                 // left is negative x
                 _left if diff_x < 0_f64 => {
                     // move to the next integer towards 0
-                    x_style.background_blue();
                     (logical_position.x - 1_f64).ceil()
                 }
                 _none => {
                     // not moving on the x axis at all
                     // not actually infinity, in effect it's 0, but expected value later
-                    x_style.background_cyan();
                     f64::INFINITY
                 }
             };
@@ -154,19 +152,16 @@ impl RayCast {
                 // down is positive y
                 _down if diff_y > 0_f64 => {
                     // move towards the next integer away from 0
-                    y_style.background_red();
                     (logical_position.y + 1_f64).floor()
                 }
                 // up is negative y
                 _up if diff_y < 0_f64 => {
                     // move towards the next integer away from 0
-                    y_style.background_yellow();
                     (logical_position.y - 1_f64).ceil()
                 }
                 _none => {
                     // not moving on the y axis at all
                     // not actually infinity, in effect it's 0 but expected value later
-                    y_style.background_purple();
                     f64::INFINITY
                 }
             };
@@ -193,12 +188,6 @@ impl RayCast {
                 } else {
                     unreachable!("We are already at the target")
                 };
-                print!(
-                    "\x1b[{};{}H{} \x1b[0m",
-                    position.y + 1,
-                    position.x + 1,
-                    abes_nice_things::Style::new().background_purple()
-                );
                 position += direction;
                 steps_taken += 1;
                 if self.record_path {
@@ -234,24 +223,6 @@ impl RayCast {
                     return (None, path);
                 }
                 position += direction;
-                match direction.axis() {
-                    Axis::Horizontal => {
-                        print!(
-                            "\x1b[{};{}H{} \x1b[0m",
-                            position.y + 1,
-                            position.x + 1,
-                            x_style
-                        )
-                    }
-                    Axis::Vertical => {
-                        print!(
-                            "\x1b[{};{}H{} \x1b[0m",
-                            position.y + 1,
-                            position.x + 1,
-                            y_style
-                        )
-                    }
-                }
                 steps_taken += 1;
                 if self.record_path {
                     path.as_mut().unwrap().push(position);
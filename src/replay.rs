@@ -0,0 +1,178 @@
+use crate::input::Input;
+use crate::state::State;
+use abes_nice_things::{FromBinary, ToBinary};
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::io::Write;
+
+// A recording is a save of the state the game started from followed by every event in the order that they
+// happened. Because the state includes the generator, feeding the events back through the same
+// logic rebuilds the same game.
+//
+// Anything loaded from outside of the game, like a save slot or a new run, is recorded whole since
+// what is on disk when replaying may be different.
+//
+// The viewport is NOT recorded, since the selector is clamped to it, replaying in a different
+// terminal size can desync
+
+/// Everything the player does which changes the game
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Event {
+    Input(Input),
+    /// What was typed into a text prompt
+    Text(String),
+    /// A save of the state which replaced the old one, None if loading it failed
+    Loaded(Option<Vec<u8>>),
+}
+impl ToBinary for Event {
+    fn to_binary(&self, binary: &mut dyn Write) -> Result<()> {
+        match self {
+            Event::Input(input) => {
+                0_u8.to_binary(binary)?;
+                input.to_binary(binary)
+            }
+            Event::Text(text) => {
+                1_u8.to_binary(binary)?;
+                text.to_binary(binary)
+            }
+            Event::Loaded(save) => {
+                2_u8.to_binary(binary)?;
+                save.as_ref().to_binary(binary)
+            }
+        }
+    }
+}
+impl FromBinary for Event {
+    fn from_binary(binary: &mut dyn std::io::prelude::Read) -> Result<Self> {
+        // This used to be a bool, which is the same as 0 and 1
+        Ok(match u8::from_binary(binary)? {
+            0 => Event::Input(Input::from_binary(binary)?),
+            1 => Event::Text(String::from_binary(binary)?),
+            2 => Event::Loaded(<Option<Vec<u8>>>::from_binary(binary)?),
+            discriminant => anyhow::bail!("Invalid event discriminant {discriminant}"),
+        })
+    }
+}
+
+/// Writes events to a recording as they happen
+pub struct Recorder {
+    file: std::io::BufWriter<std::fs::File>,
+}
+impl Recorder {
+    /// Creates the recording and writes the state it starts from
+    pub fn new(path: &str, state: &State) -> Result<Recorder> {
+        let mut recorder = Recorder {
+            file: std::io::BufWriter::new(
+                std::fs::File::create(path)
+                    .with_context(|| format!("Failed to create recording {path}"))?,
+            ),
+        };
+        crate::save::write(state, &mut recorder.file)?;
+        recorder.file.flush()?;
+        Ok(recorder)
+    }
+    /// Flushes after every event so that a crash loses as little as possible
+    pub fn record(&mut self, event: &Event) -> Result<()> {
+        event.to_binary(&mut self.file)?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// The events of a recording which have yet to be fed back
+pub struct Replay {
+    events: VecDeque<Event>,
+    /// How long to wait before each input so that it can be watched
+    delay: std::time::Duration,
+}
+impl Replay {
+    /// Reads a recording, giving the state it starts from and the events to feed back
+    pub fn load(path: &str, delay: std::time::Duration) -> Result<(State, Replay)> {
+        let mut binary = VecDeque::from(
+            std::fs::read(path).with_context(|| format!("Failed to read recording {path}"))?,
+        );
+        let state = crate::save::read(&mut binary)
+            .with_context(|| format!("Failed to read the starting state of {path}"))?;
+        let mut events = VecDeque::new();
+        while !binary.is_empty() {
+            events.push_back(
+                Event::from_binary(&mut binary)
+                    .with_context(|| format!("Failed to read event {} of {path}", events.len()))?,
+            );
+        }
+        Ok((state, Replay { events, delay }))
+    }
+    /// Gets the next input, waiting for the delay first. Returns None if the next event is not an
+    /// input
+    pub fn next_input(&mut self) -> Option<Input> {
+        if let Some(Event::Input(input)) = self.events.front() {
+            let input = *input;
+            self.events.pop_front();
+            std::thread::sleep(self.delay);
+            return Some(input);
+        }
+        None
+    }
+    /// Gets the next text input, returns None if the next event is not text
+    pub fn next_text(&mut self) -> Option<String> {
+        if let Some(Event::Text(_)) = self.events.front()
+            && let Some(Event::Text(text)) = self.events.pop_front()
+        {
+            return Some(text);
+        }
+        None
+    }
+    /// Gets the save of what was loaded next, the inner None means that loading failed. Returns
+    /// None if the next event is not a load
+    pub fn next_loaded(&mut self) -> Option<Option<Vec<u8>>> {
+        if let Some(Event::Loaded(_)) = self.events.front()
+            && let Some(Event::Loaded(save)) = self.events.pop_front()
+        {
+            return Some(save);
+        }
+        None
+    }
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Direction;
+    #[test]
+    fn event_binary() {
+        let tests = [
+            Event::Input(Input::Walk(Direction::Up)),
+            Event::Text(String::new()),
+            Event::Input(Input::Select),
+            Event::Text("some/save/file".to_string()),
+            Event::Loaded(Some(vec![1, 2, 3])),
+            Event::Loaded(None),
+        ];
+        let mut buf = VecDeque::new();
+        for test in tests.into_iter() {
+            test.to_binary(&mut buf).unwrap();
+            assert_eq!(test, Event::from_binary(&mut buf).unwrap());
+        }
+        assert_eq!(buf.len(), 0);
+    }
+    #[test]
+    fn old_event_binary() {
+        // Recordings from before there were loads used a bool to tell inputs and text apart
+        let mut buf = VecDeque::new();
+        true.to_binary(&mut buf).unwrap();
+        "text".to_string().to_binary(&mut buf).unwrap();
+        false.to_binary(&mut buf).unwrap();
+        Input::Select.to_binary(&mut buf).unwrap();
+        assert_eq!(
+            Event::from_binary(&mut buf).unwrap(),
+            Event::Text("text".to_string())
+        );
+        assert_eq!(
+            Event::from_binary(&mut buf).unwrap(),
+            Event::Input(Input::Select)
+        );
+        assert_eq!(buf.len(), 0);
+    }
+}
@@ -0,0 +1,301 @@
+use crate::state::State;
+use abes_nice_things::{FromBinary, ToBinary};
+use anyhow::{Context, Result, bail};
+use std::io::{Read, Write};
+
+// A save is laid out as
+//  magic number
+//  format version
+//  payload length
+//  payload checksum
+//  payload (the state)
+//
+// Whenever anything that gets saved changes, bump VERSION and make the FromBinary implementation
+// that changed check loading_version() so that it can still read the old layout. That way older
+// saves are migrated into the current structs as they load.
+//
+// Version history:
+//  0: saves from before there was a header, they are just the payload
+//  1: initial versioned format, the run's seed and the generator state
+//  2: the board remembers which tiles the player has seen
+//  3: noises the enemies haven't heard yet
+//  4: boss arenas
+//  5: enemies have effects
+//  6: effect trackers store how many effects there are, the player banks hasted actions
+//  7: run stats
+//  8: dungeon depth, stairs tiles
+//  9: items on the board and in the player's inventory
+// 10: the player's weapon and armor
+// 11: how many enemy types the state has characters for
+
+/// Where save slots are kept, relative to where the game was started
+pub const SAVE_DIRECTORY: &str = "saves";
+
+/// The slot used by the quicksave and quickload keys
+pub const QUICKSAVE_SLOT: &str = "quicksave";
+/// How many autosave slots are rotated through
+pub const AUTOSAVE_SLOTS: usize = 3;
+/// Saves are written to a file with this added to the name then renamed into place so that a
+/// crash while saving never leaves a half written save
+const TEMPORARY_SUFFIX: &str = ".tmp";
+
+/// The first bytes of every save, used to tell if a file is a save at all
+const MAGIC: [u8; 4] = *b"RGLK";
+/// The version of the format which is written
+pub const VERSION: u16 = 11;
+
+thread_local! {
+    static LOADING_VERSION: std::cell::Cell<u16> = const { std::cell::Cell::new(VERSION) };
+    /// Where the slots are, only tests move it
+    static DIRECTORY: std::cell::RefCell<std::path::PathBuf> =
+        std::cell::RefCell::new(SAVE_DIRECTORY.into());
+}
+/// The version of the save which is currently being loaded, when nothing is being loaded it is
+/// the current version
+pub fn loading_version() -> u16 {
+    LOADING_VERSION.get()
+}
+/// Runs the closure as if a save of the version was being loaded
+#[cfg(test)]
+pub fn with_loading_version<T>(version: u16, f: impl FnOnce() -> T) -> T {
+    LOADING_VERSION.set(version);
+    let result = f();
+    LOADING_VERSION.set(VERSION);
+    result
+}
+/// Runs the closure with the slots kept in the directory instead
+#[cfg(test)]
+pub fn with_save_directory<T>(directory: &std::path::Path, f: impl FnOnce() -> T) -> T {
+    DIRECTORY.set(directory.to_path_buf());
+    let result = f();
+    DIRECTORY.set(SAVE_DIRECTORY.into());
+    result
+}
+fn directory() -> std::path::PathBuf {
+    DIRECTORY.with_borrow(|directory| directory.clone())
+}
+/// Writes the state with a header
+pub fn write(state: &State, binary: &mut dyn Write) -> Result<()> {
+    let mut payload = Vec::new();
+    state.to_binary(&mut payload)?;
+
+    binary.write_all(&MAGIC)?;
+    VERSION.to_binary(binary)?;
+    (payload.len() as u64).to_binary(binary)?;
+    checksum(&payload).to_binary(binary)?;
+    binary.write_all(&payload)?;
+    Ok(())
+}
+/// Reads a state written by [write] with any version up to the current one, or one from before
+/// saves had a header
+pub fn read(binary: &mut dyn Read) -> Result<State> {
+    let mut magic = [0; MAGIC.len()];
+    binary
+        .read_exact(&mut magic)
+        .context("File is too short to be a save")?;
+    if magic != MAGIC {
+        // Without the header all there is to go on is whether it reads
+        let mut payload = magic.to_vec();
+        binary.read_to_end(&mut payload)?;
+        return read_payload(&payload, 0).context("File is not a save");
+    }
+    let version = u16::from_binary(binary)?;
+    if version > VERSION {
+        bail!("Unknown save version {version}, the newest this can read is {VERSION}");
+    }
+    let length = u64::from_binary(binary)?;
+    let expected_checksum = u64::from_binary(binary)?;
+
+    // Taking so that a corrupted length can't make us try to allocate everything
+    let mut payload = Vec::new();
+    binary.take(length).read_to_end(&mut payload)?;
+    if payload.len() as u64 != length {
+        bail!(
+            "Save is truncated, expected {length} bytes but got {}",
+            payload.len()
+        );
+    }
+    if checksum(&payload) != expected_checksum {
+        bail!("Save is corrupted, the checksum does not match");
+    }
+    read_payload(&payload, version)
+}
+/// Reads the state, migrating it from the version
+fn read_payload(payload: &[u8], version: u16) -> Result<State> {
+    LOADING_VERSION.set(version);
+    let mut remaining = payload;
+    let state = State::from_binary(&mut remaining);
+    LOADING_VERSION.set(VERSION);
+    let state = state.with_context(|| format!("Failed to read version {version} save"))?;
+    if !remaining.is_empty() {
+        bail!("Save has {} bytes of unread data", remaining.len());
+    }
+    Ok(state)
+}
+/// Gets the names of all the save slots, sorted. If there are none or the directory can't be read
+/// then it is empty
+pub fn list_slots() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(directory()) else {
+        return Vec::new();
+    };
+    let mut slots: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| !name.ends_with(TEMPORARY_SUFFIX))
+        .collect();
+    slots.sort();
+    slots
+}
+/// Gets the slot which was saved to most recently, None if there are no saves
+pub fn latest_slot() -> Option<String> {
+    list_slots().into_iter().max_by_key(|slot| {
+        slot_path(slot)
+            .and_then(|path| Ok(std::fs::metadata(path)?.modified()?))
+            .ok()
+    })
+}
+pub fn slot_exists(name: &str) -> bool {
+    slot_path(name).is_ok_and(|path| path.is_file())
+}
+/// Gets the path of the slot, the name must be usable as a file name on its own
+pub fn slot_path(name: &str) -> Result<std::path::PathBuf> {
+    if name.is_empty() {
+        bail!("Save name cannot be empty");
+    }
+    if name.contains(['/', '\\']) || name == "." || name == ".." {
+        bail!("Save name cannot be a path: {name}");
+    }
+    Ok(directory().join(name))
+}
+/// Gets the name of the autosave slot to use for the given autosave
+pub fn autosave_slot(autosave_number: usize) -> String {
+    format!("autosave-{}", autosave_number % AUTOSAVE_SLOTS)
+}
+/// Saves atomically, either the whole save ends up in the slot or the slot is left alone
+pub fn save_to_slot(state: &State, name: &str) -> Result<()> {
+    let path = slot_path(name)?;
+    let temporary_path = slot_path(&format!("{name}{TEMPORARY_SUFFIX}"))?;
+    let directory = directory();
+    std::fs::create_dir_all(&directory)
+        .with_context(|| format!("Failed to create {}", directory.display()))?;
+    let mut file = std::io::BufWriter::new(
+        std::fs::File::create(&temporary_path)
+            .with_context(|| format!("Failed to create {}", temporary_path.display()))?,
+    );
+    write(state, &mut file)?;
+    file.into_inner()
+        .map_err(|error| error.into_error())?
+        .sync_all()?;
+    std::fs::rename(&temporary_path, &path)
+        .with_context(|| format!("Failed to move save into {}", path.display()))?;
+    Ok(())
+}
+pub fn load_from_slot(name: &str) -> Result<State> {
+    let path = slot_path(name)?;
+    if !path.is_file() {
+        bail!("There is no save called {name}");
+    }
+    let mut file = std::io::BufReader::new(
+        std::fs::File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?,
+    );
+    read(&mut file)
+}
+/// FNV-1a, it only needs to catch corruption, not tampering
+fn checksum(payload: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in payload.iter() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn header(version: u16, payload: &[u8], checksum: u64) -> Vec<u8> {
+        let mut binary = MAGIC.to_vec();
+        version.to_binary(&mut binary).unwrap();
+        (payload.len() as u64).to_binary(&mut binary).unwrap();
+        checksum.to_binary(&mut binary).unwrap();
+        binary.extend_from_slice(payload);
+        binary
+    }
+    #[test]
+    fn not_a_save() {
+        assert!(read(&mut b"nope, not a save".as_slice()).is_err());
+        assert!(read(&mut b"RG".as_slice()).is_err());
+    }
+    #[test]
+    fn unknown_version() {
+        let payload = [1, 2, 3];
+        for version in [VERSION + 1, u16::MAX] {
+            let binary = header(version, &payload, checksum(&payload));
+            let error = read(&mut binary.as_slice()).err().unwrap();
+            assert!(error.to_string().contains("Unknown save version"));
+        }
+    }
+    #[test]
+    fn version_0() {
+        let mut state = State::blank_headless();
+        state.player.position = crate::math::Vector::new(3, 2);
+        state.total_turns = 7;
+        let mut binary = Vec::new();
+        state.write_version_0(&mut binary).unwrap();
+        let rng = crate::random::get_state();
+        let mut loaded = read(&mut binary.as_slice()).unwrap();
+        assert_eq!(loaded.player.position, state.player.position);
+        assert_eq!(loaded.total_turns, 7);
+        assert_eq!(loaded.seed, 0);
+        // There is no generator state in it so the current one carries on
+        loaded.finish_load();
+        assert_eq!(crate::random::get_state(), rng);
+    }
+    #[test]
+    fn corrupted() {
+        let payload = [1, 2, 3];
+        let binary = header(VERSION, &payload, checksum(&payload) ^ 1);
+        let error = read(&mut binary.as_slice()).err().unwrap();
+        assert!(error.to_string().contains("corrupted"));
+    }
+    #[test]
+    fn failed_load_changes_nothing() {
+        let mut state = State::blank_headless();
+        let mut payload = Vec::new();
+        state.to_binary(&mut payload).unwrap();
+        let corrupted = header(VERSION, &payload, checksum(&payload) ^ 1);
+        payload.push(0);
+        let unread = header(VERSION, &payload, checksum(&payload));
+
+        crate::random::seed(1);
+        state.simulate([crate::input::Input::Walk(crate::math::Direction::Right); 2]);
+        let position = state.player.position;
+        let mut board = Vec::new();
+        state.board.to_binary(&mut board).unwrap();
+        let rng = crate::random::get_state();
+        for binary in [corrupted, unread] {
+            assert!(state.load(|| read(&mut binary.as_slice())).is_err());
+            assert_eq!(state.player.position, position);
+            let mut after = Vec::new();
+            state.board.to_binary(&mut after).unwrap();
+            assert_eq!(after, board);
+            assert_eq!(crate::random::get_state(), rng);
+        }
+    }
+    #[test]
+    fn slot_names() {
+        assert!(slot_path("fine").is_ok());
+        assert!(slot_path("").is_err());
+        assert!(slot_path("../escape").is_err());
+        assert!(slot_path("..").is_err());
+        assert!(slot_path("nested/save").is_err());
+    }
+    #[test]
+    fn truncated() {
+        let payload = [1, 2, 3];
+        let mut binary = header(VERSION, &payload, checksum(&payload));
+        binary.pop();
+        let error = read(&mut binary.as_slice()).err().unwrap();
+        assert!(error.to_string().contains("truncated"));
+    }
+}
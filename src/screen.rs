@@ -0,0 +1,227 @@
+use crate::math::Vector;
+use abes_nice_things::Style;
+use std::io::Write;
+
+/// A single character on the screen and how it is styled
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Cell {
+    ch: char,
+    /// The escape codes which set the style, empty for no style
+    style: String,
+}
+impl Cell {
+    fn blank() -> Cell {
+        Cell {
+            ch: ' ',
+            style: String::new(),
+        }
+    }
+}
+
+/// Everything gets drawn into the back buffer, then only the cells which are different from the
+/// front buffer (what is on the terminal) get sent.
+///
+/// Positions are 0 indexed, unlike the escape codes.
+#[derive(Debug)]
+pub struct Screen {
+    size: Vector<usize>,
+    back: Vec<Cell>,
+    /// None is when we don't know what is on the terminal there
+    front: Vec<Option<Cell>>,
+}
+impl Default for Screen {
+    fn default() -> Self {
+        Screen::new(Vector::ZERO)
+    }
+}
+impl Screen {
+    pub fn new(size: Vector<usize>) -> Screen {
+        Screen {
+            size,
+            back: vec![Cell::blank(); size.x * size.y],
+            front: vec![None; size.x * size.y],
+        }
+    }
+    pub fn get_size(&self) -> Vector<usize> {
+        self.size
+    }
+    /// Forgets what is on the terminal so that everything gets sent next flush. This is needed
+    /// whenever something gets written to the terminal without going through here.
+    pub fn invalidate(&mut self) {
+        self.front.fill(None);
+    }
+    /// Changes the size, this throws away the back buffer and invalidates
+    pub fn resize(&mut self, size: Vector<usize>) {
+        *self = Screen::new(size);
+    }
+    fn index(&self, position: Vector<usize>) -> Option<usize> {
+        if position.x >= self.size.x || position.y >= self.size.y {
+            return None;
+        }
+        Some(position.y * self.size.x + position.x)
+    }
+    /// Sets a cell in the back buffer, anything off screen is ignored
+    pub fn set(&mut self, position: Vector<usize>, ch: char, style: Option<Style>) {
+        if let Some(index) = self.index(position) {
+            self.back[index] = Cell {
+                ch,
+                style: style.map(|style| style.to_string()).unwrap_or_default(),
+            };
+        }
+    }
+    /// Writes text which can contain style escape codes, going right from the position. Anything
+    /// off screen is cut off and a new line goes down a row back to the starting column. Escape
+    /// codes other than styles are ignored.
+    pub fn write(&mut self, position: Vector<usize>, text: &str) {
+        self.write_clipped(position, self.size.x, text);
+    }
+    /// Like [Screen::write] but anything past width columns from the position is cut off too
+    pub fn write_clipped(&mut self, position: Vector<usize>, width: usize, text: &str) {
+        let mut style = String::new();
+        let mut cursor = position;
+        let mut chars = text.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\x1b' => match chars.next() {
+                    Some('[') => {
+                        let mut sequence = String::from("\x1b[");
+                        for ch in chars.by_ref() {
+                            sequence.push(ch);
+                            // The final byte of a control sequence
+                            if ('@'..='~').contains(&ch) {
+                                break;
+                            }
+                        }
+                        if sequence.ends_with('m') {
+                            if sequence == "\x1b[0m" || sequence == "\x1b[m" {
+                                style.clear();
+                            } else {
+                                style.push_str(&sequence);
+                            }
+                        }
+                    }
+                    // Character set changes and the like have one more character
+                    Some(_) => {
+                        chars.next();
+                    }
+                    None => {}
+                },
+                '\n' => {
+                    cursor.x = position.x;
+                    cursor.y += 1;
+                }
+                '\r' => cursor.x = position.x,
+                ch => {
+                    if cursor.x - position.x < width
+                        && let Some(index) = self.index(cursor)
+                    {
+                        self.back[index] = Cell {
+                            ch,
+                            style: style.clone(),
+                        };
+                    }
+                    cursor.x += 1;
+                }
+            }
+        }
+    }
+    /// Sends everything which changed since the last flush and puts the cursor at the position.
+    /// After this the back buffer is blank and ready for the next frame.
+    pub fn flush(&mut self, out: &mut impl Write, cursor: Vector<usize>) -> std::io::Result<()> {
+        let mut buffer = Vec::new();
+        // Where the terminal's cursor is, None if we don't know
+        let mut terminal_cursor = None;
+        let mut current_style = String::new();
+        write!(buffer, "\x1b[0m")?;
+        for index in 0..self.back.len() {
+            if self.front[index].as_ref() == Some(&self.back[index]) {
+                continue;
+            }
+            let position = Vector::new(index % self.size.x, index / self.size.x);
+            if terminal_cursor != Some(position) {
+                write!(buffer, "\x1b[{};{}H", position.y + 1, position.x + 1)?;
+            }
+            let cell = &self.back[index];
+            if cell.style != current_style {
+                write!(buffer, "\x1b[0m{}", cell.style)?;
+                current_style.clone_from(&cell.style);
+            }
+            write!(buffer, "{}", cell.ch)?;
+            // Writing in the last column doesn't move the cursor so we don't know where it is
+            terminal_cursor = if position.x + 1 < self.size.x {
+                Some(Vector::new(position.x + 1, position.y))
+            } else {
+                None
+            };
+        }
+        write!(buffer, "\x1b[0m\x1b[{};{}H", cursor.y + 1, cursor.x + 1)?;
+        out.write_all(&buffer)?;
+        out.flush()?;
+
+        for (front, back) in self.front.iter_mut().zip(self.back.iter_mut()) {
+            *front = Some(std::mem::replace(back, Cell::blank()));
+        }
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn write_styles() {
+        let mut screen = Screen::new(Vector::new(5, 2));
+        screen.write(Vector::new(1, 0), "\x1b[31mab\x1b[0mc\nd");
+        assert_eq!(screen.back[1].ch, 'a');
+        assert_eq!(screen.back[1].style, "\x1b[31m");
+        assert_eq!(screen.back[2].ch, 'b');
+        assert_eq!(screen.back[2].style, "\x1b[31m");
+        assert_eq!(screen.back[3].ch, 'c');
+        assert_eq!(screen.back[3].style, "");
+        assert_eq!(screen.back[6].ch, 'd');
+    }
+    #[test]
+    fn write_cut_off() {
+        let mut screen = Screen::new(Vector::new(2, 1));
+        screen.write(Vector::new(1, 0), "abc\ndef");
+        assert_eq!(screen.back[0], Cell::blank());
+        assert_eq!(screen.back[1].ch, 'a');
+    }
+    #[test]
+    fn write_clipped() {
+        let mut screen = Screen::new(Vector::new(5, 2));
+        screen.write_clipped(Vector::new(1, 0), 2, "abc\ndef");
+        assert_eq!(screen.back[1].ch, 'a');
+        assert_eq!(screen.back[2].ch, 'b');
+        assert_eq!(screen.back[3], Cell::blank());
+        assert_eq!(screen.back[6].ch, 'd');
+        assert_eq!(screen.back[7].ch, 'e');
+        assert_eq!(screen.back[8], Cell::blank());
+    }
+    #[test]
+    fn only_changes_are_sent() {
+        let mut screen = Screen::new(Vector::new(4, 2));
+        screen.write(Vector::ZERO, "abcd\nefgh");
+        let mut first = Vec::new();
+        screen.flush(&mut first, Vector::ZERO).unwrap();
+        let first = String::from_utf8(first).unwrap();
+        assert!(first.contains("abcd"));
+        assert!(first.contains("efgh"));
+
+        screen.write(Vector::ZERO, "abcd\nefXh");
+        let mut second = Vec::new();
+        screen.flush(&mut second, Vector::ZERO).unwrap();
+        let second = String::from_utf8(second).unwrap();
+        assert_eq!(second, "\x1b[0m\x1b[2;3HX\x1b[0m\x1b[1;1H");
+    }
+    #[test]
+    fn invalidate_resends() {
+        let mut screen = Screen::new(Vector::new(2, 1));
+        screen.write(Vector::ZERO, "ab");
+        screen.flush(&mut Vec::new(), Vector::ZERO).unwrap();
+        screen.invalidate();
+        screen.write(Vector::ZERO, "ab");
+        let mut output = Vec::new();
+        screen.flush(&mut output, Vector::ZERO).unwrap();
+        assert!(String::from_utf8(output).unwrap().contains("ab"));
+    }
+}
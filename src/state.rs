@@ -1,16 +1,28 @@
 use crate::board::Board;
 use crate::context_menu::ContextMenu;
 use crate::context_menu::ContextMenuID;
+use crate::input::Input;
 use crate::math::*;
 use crate::player::Player;
+use crate::screen::Screen;
 use abes_nice_things::{FromBinary, ToBinary};
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
 use std::io::Write;
 
+/// The screen size used when there is no terminal, it only matters for the viewport
+pub const HEADLESS_SCREEN_SIZE: Vector<usize> = Vector::new(80, 24);
+
 pub struct State {
     pub board: Board,
     pub player: Player,
     pub total_turns: usize,
+    /// The seed which the run was started with, along with the saved generator state this is
+    /// enough to reproduce everything random
+    pub seed: u64,
+    /// What has happened so far this run, shown when the player dies
+    pub stats: RunStats,
+    pub dungeon: crate::dungeon::Dungeon,
     pub screen_size: Vector<usize>,
     context_menu_stack: crate::context_menu::Stack,
     /// Whether or not the player is controlling th context menu
@@ -19,12 +31,28 @@ pub struct State {
     pub feedback: String,
     enemy_visuals: [Option<char>; crate::enemy::VTABLES.len()],
     next_enemy_visual: u8,
+    /// This does not get saved
+    interface: Interface,
+    /// Where events get recorded to, this does not get saved
+    recorder: Option<crate::replay::Recorder>,
+    /// The recording being fed back, this does not get saved
+    pub replay: Option<crate::replay::Replay>,
+    /// How many turns between autosaves, None means no autosaving. This does not get saved
+    pub autosave_interval: Option<usize>,
+    /// What is and is going to be on the terminal, this does not get saved
+    pub screen: Screen,
+    /// The generator state read from a save, it only replaces the running generator once the
+    /// whole save has been read successfully. See [State::finish_load]
+    loaded_rng: Option<crate::random::Rng>,
 }
 impl ToBinary for State {
     fn to_binary(&self, binary: &mut dyn Write) -> Result<()> {
         self.board.to_binary(binary)?;
         self.player.to_binary(binary)?;
         self.total_turns.to_binary(binary)?;
+        self.seed.to_binary(binary)?;
+        self.stats.to_binary(binary)?;
+        self.dungeon.to_binary(binary)?;
         // Screen size cannot be usefully saved
         self.context_menu_stack.len().to_binary(binary)?;
         for (argument, index, menu) in self.context_menu_stack.iter() {
@@ -34,59 +62,366 @@ impl ToBinary for State {
         }
         self.context_menu_inputs.to_binary(binary)?;
         self.feedback.to_binary(binary)?;
+        self.enemy_visuals.len().to_binary(binary)?;
         for enemy_visual in self.enemy_visuals.iter() {
             enemy_visual.as_ref().to_binary(binary)?;
         }
-        self.next_enemy_visual.to_binary(binary)
+        self.next_enemy_visual.to_binary(binary)?;
+        // The generator has to continue from where it was or the run will diverge
+        crate::random::get_state().to_binary(binary)
     }
 }
 impl FromBinary for State {
     fn from_binary(binary: &mut dyn std::io::prelude::Read) -> Result<Self> {
+        State::read(binary, Interface::Terminal)
+    }
+}
+impl State {
+    /// Reads a saved state, the terminal is only asked for its size if that is the interface
+    fn read(binary: &mut dyn std::io::prelude::Read, interface: Interface) -> Result<State> {
+        let screen_size = match interface {
+            Interface::Terminal => crate::terminal::get_size().unwrap_or(HEADLESS_SCREEN_SIZE),
+            Interface::Headless(_) => HEADLESS_SCREEN_SIZE,
+        };
         let mut state = State {
             board: Board::from_binary(binary)?,
             player: Player::from_binary(binary)?,
             total_turns: usize::from_binary(binary)?,
-            screen_size: crate::get_terminal_size(),
+            // Before version 1 the seed wasn't known
+            seed: if crate::save::loading_version() >= 1 {
+                u64::from_binary(binary)?
+            } else {
+                0
+            },
+            // Before version 7 there were no stats
+            stats: if crate::save::loading_version() >= 7 {
+                RunStats::from_binary(binary)?
+            } else {
+                RunStats::default()
+            },
+            // Before version 8 there was only one floor, this gets fixed up once the board is
+            // there to take the size from
+            dungeon: if crate::save::loading_version() >= 8 {
+                crate::dungeon::Dungeon::from_binary(binary)?
+            } else {
+                crate::dungeon::Dungeon::new(crate::board::AxisLength::Full, 0)
+            },
+            screen_size,
             context_menu_stack: crate::context_menu::Stack::from_binary(binary)?,
             context_menu_inputs: bool::from_binary(binary)?,
             feedback: String::from_binary(binary)?,
-            enemy_visuals: <[Option<char>; crate::enemy::VTABLES.len()]>::from_binary(binary)?,
+            enemy_visuals: read_enemy_visuals(binary)?,
             next_enemy_visual: u8::from_binary(binary)?,
+            interface,
+            recorder: None,
+            replay: None,
+            autosave_interval: None,
+            screen: Screen::default(),
+            loaded_rng: None,
         };
-        state.finish_load_effects();
+        // Before version 1 the generator wasn't saved, it carries on from wherever it is
+        if crate::save::loading_version() >= 1 {
+            state.loaded_rng = Some(crate::random::Rng::from_binary(binary)?);
+        }
+        if crate::save::loading_version() < 8 {
+            state.dungeon = crate::dungeon::Dungeon::new(
+                state.board.axis_length(),
+                crate::dungeon::Dungeon::DEFAULT_BUDGET,
+            );
+        }
+        state.fit_viewport();
+        Board::update_field_of_view(&mut state);
         Ok(state)
     }
-}
-impl State {
-    pub fn new(board: Board, player: Player, screen_size: Vector<usize>) -> State {
-        State {
+    /// The seed should be the one the generator was seeded with before the board was generated
+    /// and the budget the one it was generated with, floors further down are based on it
+    pub fn new(
+        board: Board,
+        player: Player,
+        screen_size: Vector<usize>,
+        seed: u64,
+        budget: usize,
+    ) -> State {
+        let dungeon = crate::dungeon::Dungeon::new(board.axis_length(), budget);
+        let mut state = State {
             board,
             player,
             total_turns: 0,
+            seed,
+            stats: RunStats::default(),
+            dungeon,
             screen_size,
             context_menu_stack: vec![(None, 0, ContextMenuID::default())],
             context_menu_inputs: false,
             feedback: String::new(),
             enemy_visuals: [None; crate::enemy::VTABLES.len()],
             next_enemy_visual: 0,
+            interface: Interface::Terminal,
+            recorder: None,
+            replay: None,
+            autosave_interval: None,
+            screen: Screen::default(),
+            loaded_rng: None,
+        };
+        Board::update_field_of_view(&mut state);
+        state
+    }
+    /// Creates a state which never touches the terminal, rendering does nothing and text prompts
+    /// are answered from [State::push_text_input]
+    pub fn new_headless(board: Board, player: Player, seed: u64, budget: usize) -> State {
+        let mut state = State::new(board, player, HEADLESS_SCREEN_SIZE, seed, budget);
+        state.interface = Interface::Headless(VecDeque::new());
+        state
+    }
+    /// Loads a state which never touches the terminal, like [State::new_headless]
+    pub fn load_headless(binary: &mut dyn std::io::prelude::Read) -> Result<State> {
+        let mut state = State::read(binary, Interface::Headless(VecDeque::new()))?;
+        state.finish_load();
+        Ok(state)
+    }
+    /// Seeds the generator then generates a board and creates a headless state for it
+    pub fn generate_headless(
+        axis_length: crate::board::AxisLength,
+        budget: usize,
+        seed: u64,
+    ) -> Result<State> {
+        crate::random::seed(seed);
+        let board = crate::board::map_gen::generate(axis_length, HEADLESS_SCREEN_SIZE, budget)?;
+        Ok(State::new_headless(
+            board,
+            Player::new(Vector::new(1, 1)),
+            seed,
+            budget,
+        ))
+    }
+    pub fn is_headless(&self) -> bool {
+        matches!(self.interface, Interface::Headless(_))
+    }
+    /// Queues up an answer for the next text prompt, this only does anything when headless
+    pub fn push_text_input(&mut self, text: String) {
+        if let Interface::Headless(queue) = &mut self.interface {
+            queue.push_back(text);
+        }
+    }
+    /// Runs the inputs one after another exactly like they were pressed but without rendering
+    pub fn simulate(&mut self, inputs: impl IntoIterator<Item = Input>) {
+        for input in inputs.into_iter() {
+            self.handle_input(input);
+        }
+    }
+    /// Starts recording everything from the current state onwards
+    pub fn start_recording(&mut self, path: &str) -> Result<()> {
+        self.recorder = Some(crate::replay::Recorder::new(path, self)?);
+        Ok(())
+    }
+    /// Records the event, if it fails then recording stops and the player gets told why
+    fn record(&mut self, event: crate::replay::Event) {
+        if let Some(recorder) = self.recorder.as_mut()
+            && let Err(error) = recorder.record(&event)
+        {
+            self.recorder = None;
+            self.feedback = format!("Stopped recording: {error}");
+        }
+    }
+    /// Gets the next input from the replay if there is one, otherwise from the terminal
+    pub fn next_input(&mut self) -> Input {
+        let input = if self.is_terminal_too_small() {
+            // Nothing can be seen so nothing should be done. Whether keys do anything then
+            // depends on the terminal rather than the game, so they are dropped before they can
+            // be recorded and the replay waits for the terminal to be big enough
+            loop {
+                if Input::get() == Input::Resize {
+                    break Input::Resize;
+                }
+            }
+        } else {
+            match self.replay.as_mut().and_then(|replay| replay.next_input()) {
+                Some(input) => input,
+                None => {
+                    self.end_replay();
+                    Input::get()
+                }
+            }
+        };
+        self.record(crate::replay::Event::Input(input));
+        input
+    }
+    /// Stops feeding back the replay and gives control back to the player
+    fn end_replay(&mut self) {
+        if let Some(replay) = self.replay.take() {
+            self.feedback = if replay.is_finished() {
+                "Replay finished".to_string()
+            } else {
+                "Replay desynced, stopping it".to_string()
+            };
+            self.render();
+        }
+    }
+    /// Does whatever the input means and increments the turn if it took one
+    pub fn handle_input(&mut self, input: Input) {
+        // Anything that would act in the world just lets the turn pass instead, menus check for
+        // themselves in act_from_menu
+        if match input {
+            Input::Walk(_)
+            | Input::SkipTurn
+            | Input::UseAbility(_)
+            | Input::PickUp
+            | Input::Drop => true,
+            Input::Select => !self.context_menu_inputs,
+            _ => false,
+        } && self.check_stunned()
+        {
+            self.increment();
+            return;
+        }
+        if match input {
+            Input::Walk(direction) => Player::handle_walk_input(self, direction),
+            Input::MoveSelector(direction) => self.handle_move_selector_input(direction),
+            Input::ChangeRenderTarget => {
+                Player::handle_change_render_target_input(self);
+                false
+            }
+            Input::ToggleContextMenu => self.handle_toggle_context_menu_input(),
+            Input::Select => self.handle_select_input(),
+            Input::SkipTurn => true,
+            Input::Resize => {
+                self.handle_resize();
+                false
+            }
+            Input::QuickSave => {
+                self.save_to_slot(crate::save::QUICKSAVE_SLOT);
+                false
+            }
+            Input::QuickLoad => {
+                self.load_from_slot(crate::save::QUICKSAVE_SLOT);
+                false
+            }
+            Input::UseAbility(ability) => crate::ability::use_ability(self, ability),
+            Input::PickUp => crate::item::pick_up(self),
+            Input::Drop => {
+                let last = self.player.inventory.len().saturating_sub(1);
+                crate::item::drop(self, last)
+            }
+        } {
+            self.finish_action();
+        }
+    }
+    /// Tells the player that they can't act if they are stunned, returns if they are
+    fn check_stunned(&mut self) -> bool {
+        if self
+            .player
+            .effect_tracker
+            .has(crate::effect::EffectID::Stun)
+        {
+            self.feedback = "You are stunned".to_string();
+            return true;
+        }
+        false
+    }
+    /// Does something picked from a context menu which takes a turn if it went through. Returns
+    /// if it went through, it never does while stunned
+    pub fn act_from_menu(&mut self, action: impl FnOnce(&mut State) -> bool) -> bool {
+        if self.check_stunned() || !action(self) {
+            return false;
+        }
+        self.finish_action();
+        true
+    }
+    /// Lets time pass after the player did something which takes a turn. While hasted every other
+    /// action is free and while slowed every action takes two turns
+    pub fn finish_action(&mut self) {
+        use crate::effect::EffectID;
+        let tracker = &self.player.effect_tracker;
+        match (tracker.has(EffectID::Haste), tracker.has(EffectID::Slow)) {
+            (true, false) => {
+                self.player.free_action ^= true;
+                if !self.player.free_action {
+                    self.increment();
+                }
+            }
+            (false, true) => {
+                self.increment();
+                self.increment();
+            }
+            _ => self.increment(),
+        }
+    }
+    /// Gets the new size of the terminal and fits everything to it
+    pub fn handle_resize(&mut self) {
+        if self.is_headless() {
+            return;
+        }
+        if let Some(screen_size) = crate::terminal::get_size() {
+            self.screen_size = screen_size;
         }
+        self.fit_viewport();
+        // Everything moved so nothing on screen can be trusted
+        print!("\x1b[2J");
+        self.screen.resize(self.screen_size);
     }
-    /// Clear the screen and draw the board, the player, enemies, everything
+    /// Gets rid of everything which pointed into the old board after it is replaced
+    pub fn reset_for_new_board(&mut self) {
+        self.context_menu_stack = vec![(None, 0, ContextMenuID::default())];
+        self.fit_viewport();
+        self.screen.invalidate();
+    }
+    /// Makes the viewport as big as the screen allows
+    fn fit_viewport(&mut self) {
+        self.board.set_viewport_size(
+            crate::calc_desired_dimensions(self.screen_size).unwrap_or(crate::MINIMUM_VIEWPORT),
+        );
+    }
+    pub fn is_terminal_too_small(&self) -> bool {
+        !self.is_headless() && crate::calc_desired_dimensions(self.screen_size).is_none()
+    }
+    /// Tells the player to make the terminal bigger instead of drawing something broken
+    fn render_too_small(&mut self) {
+        let minimum = crate::minimum_screen_size();
+        print!(
+            "\x1b[H\x1b[2J{}Terminal too small\x1b[0m\r\n\
+            Currently {}x{}\r\n\
+            Needs at least {}x{}",
+            abes_nice_things::Style::new().red().intense(true),
+            self.screen_size.x,
+            self.screen_size.y,
+            minimum.x,
+            minimum.y
+        );
+        std::io::stdout().flush().unwrap();
+        self.screen.invalidate();
+    }
+    /// Draw the board, the player, enemies, everything then send only what changed since the last
+    /// frame
     pub fn render(&mut self) {
+        if self.is_headless() {
+            return;
+        }
+        if self.is_terminal_too_small() {
+            self.render_too_small();
+            return;
+        }
         let center = self.player.get_render_target_pos();
         let viewport = self.board.calculate_viewport(center);
-        let mut buffer = Vec::new();
-
-        self.board.render_tiles(viewport, &mut buffer);
-        Board::render_enemies(self, viewport, &mut buffer);
-        self.player.render(viewport, &mut buffer);
-        self.render_meta_ui(&mut buffer);
-        crate::context_menu::ContextMenu::render(self, &mut buffer);
+        if self.screen.get_size() != self.screen_size {
+            self.screen.resize(self.screen_size);
+        }
+        // Taken out so that it can be drawn to while everything else is borrowed
+        let mut screen = std::mem::take(&mut self.screen);
 
-        self.player.position_cursor(viewport, &mut buffer);
+        self.board.render_tiles(viewport, &mut screen);
+        self.board.render_items(viewport, &mut screen);
+        Board::render_enemies(self, viewport, &mut screen);
+        self.player.render(viewport, &mut screen);
+        self.render_meta_ui(&mut screen);
+        crate::context_menu::ContextMenu::render(self, &mut screen);
 
-        std::io::stdout().write_all(&buffer).unwrap();
-        std::io::stdout().flush().unwrap();
+        screen
+            .flush(
+                &mut std::io::stdout(),
+                self.player.get_cursor_position(viewport),
+            )
+            .unwrap();
+        self.screen = screen;
     }
     /// Handles the select input (enter) and returns if the turn should be incremented
     pub fn handle_select_input(&mut self) -> bool {
@@ -114,7 +449,6 @@ impl State {
             false
         } else {
             const INTERACT_RANGE: usize = 3;
-            const SMACK_RANGE: usize = 1;
             let no_range_limit = self.player.no_interact_range_limit;
             if !(no_range_limit
                 || self
@@ -131,9 +465,17 @@ impl State {
                 && self
                     .player
                     .position
-                    .is_near(self.player.selector, SMACK_RANGE)
+                    .is_near(self.player.selector, self.player.get_stats().reach)
+                // Reach doesn't go through walls, closed doors or other enemies
+                && crate::raycast::RayCast::new(self.player.position, self.player.selector)
+                    .resolve(self)
+                    .0
+                    == Some(MapObject::Enemy(id))
             {
                 Player::attack(self, id);
+            } else if self.board.is_door_locked(self.player.selector) {
+                self.feedback = "The door is sealed".to_string();
+                return false;
             // Importantly, you must not be able to close a door while an enemy is on it
             } else if let Some(crate::board::tile::Tile::Door { open, .. }) =
                 &mut self.board[self.player.selector]
@@ -141,6 +483,8 @@ impl State {
                 && enemy_at_selector.is_none()
             {
                 *open = !*open;
+                self.board
+                    .make_noise(self.player.selector, crate::board::Noise::DOOR);
             } else {
                 return false;
             }
@@ -178,11 +522,7 @@ impl State {
                     }
                 }
                 // unrecurse back up
-                Direction::Left => {
-                    if self.context_menu_stack.len() > 1 {
-                        self.context_menu_stack.pop();
-                    }
-                }
+                Direction::Left => self.leave_context_menu(),
                 // recurse deeper and DON'T run actions
                 Direction::Right => {
                     if options_len == 0 {
@@ -213,6 +553,29 @@ impl State {
         self.total_turns += 1;
         Board::increment(self);
         Player::increment(self);
+        if let Some(crate::board::tile::Tile::Stairs) = self.board[self.player.position]
+            && let Err(error) = crate::dungeon::Dungeon::descend(self)
+        {
+            self.feedback = format!("{error:#}");
+        }
+        Board::update_field_of_view(self);
+        self.autosave();
+    }
+    /// Saves to the next autosave slot if enough turns have passed, the player is only told if it
+    /// fails. A dead player is never saved since loading the last save would just bring back the
+    /// corpse
+    fn autosave(&mut self) {
+        if !self.player.is_dead()
+            && self.can_write_saves()
+            && let Some(interval) = self.autosave_interval
+            && interval != 0
+            && self.total_turns % interval == 0
+        {
+            let slot = crate::save::autosave_slot(self.total_turns / interval);
+            if let Err(error) = crate::save::save_to_slot(self, &slot) {
+                self.feedback = format!("Failed to autosave to {slot}: {error:#}");
+            }
+        }
     }
     pub fn is_reachable(&self, position: Vector<usize>) -> bool {
         // Make it using the rooms for memoization
@@ -227,23 +590,31 @@ impl State {
     pub fn get_current_context_menu_argument(&self) -> &Option<crate::context_menu::Argument> {
         &self.context_menu_stack.last().unwrap().0
     }
+    /// Goes back up to the previous context menu, unless it is already at the top
+    pub fn leave_context_menu(&mut self) {
+        if self.context_menu_stack.len() > 1 {
+            self.context_menu_stack.pop();
+        }
+    }
     pub fn get_context_menu_selector_mut(&mut self) -> &mut usize {
         &mut self.context_menu_stack.last_mut().unwrap().1
     }
-    pub fn render_meta_ui(&self, buffer: &mut impl Write) {
+    pub fn render_meta_ui(&self, screen: &mut Screen) {
         // all meta ui positions are based on the viewport's height and so are given as offsets
         // 1: feedback
         // 2: health bar
         // 3: energy bar
         // 4: meta info
-        // 5: input
-        //
-        // We don't need to clear this section of screen because drawing the tiles already does
+        // 5: equipment
+        // 6: input
         let base_height = self.board.get_viewport_size().y;
+        // Anything wider than the viewport would run into the context menu's column
+        let width = self.board.get_viewport_size().x;
         // feedback
-        writeln!(buffer, "\x1b[{};0H{}", base_height + 2, self.feedback).unwrap();
+        screen.write_clipped(Vector::new(0, base_height + 1), width, &self.feedback);
 
         // health bar
+        let mut bar = Vec::new();
         abes_nice_things::ProgressBar::new(
             self.player.health,
             self.player.max_health,
@@ -253,11 +624,16 @@ impl State {
         .done_char('#')
         .header_char('#')
         .done_style(*abes_nice_things::Style::new().green().intense(true))
-        .draw_to(buffer)
+        .draw_to(&mut bar)
         .unwrap();
-        writeln!(buffer).unwrap();
+        screen.write_clipped(
+            Vector::new(0, base_height + 2),
+            width,
+            &String::from_utf8_lossy(&bar),
+        );
 
         // energy bar
+        let mut bar = Vec::new();
         abes_nice_things::ProgressBar::new(
             self.player.energy,
             self.player.max_energy,
@@ -267,23 +643,62 @@ impl State {
         .done_char('#')
         .header_char('#')
         .done_style(*abes_nice_things::Style::new().cyan().intense(true))
-        .draw_to(buffer)
+        .draw_to(&mut bar)
         .unwrap();
-        writeln!(buffer).unwrap();
+        screen.write_clipped(
+            Vector::new(0, base_height + 3),
+            width,
+            &String::from_utf8_lossy(&bar),
+        );
 
         // meta info
-        write!(
-            buffer,
-            "Selector: {}, Turn: {}, Local turn: {}",
-            self.player.selector,
-            self.total_turns,
-            self.board.get_local_turn(),
-        )
-        .unwrap();
+        screen.write_clipped(
+            Vector::new(0, base_height + 4),
+            width,
+            &format!(
+                "Depth: {}, Selector: {}, Turn: {}, Local turn: {}, Seed: {}",
+                self.dungeon.depth,
+                self.player.selector,
+                self.total_turns,
+                self.board.get_local_turn(),
+                self.seed,
+            ),
+        );
+
+        // equipment
+        let stats = self.player.get_stats();
+        let name = |slot| {
+            self.player
+                .get_equipped(slot)
+                .map_or("None", |item: crate::item::ItemID| item.get().name)
+        };
+        screen.write_clipped(
+            Vector::new(0, base_height + 5),
+            width,
+            &format!(
+                "Weapon: {}, Armor: {}, Damage: {}-{}, Reach: {}, Defense: {}",
+                name(crate::item::Slot::Weapon),
+                name(crate::item::Slot::Armor),
+                stats.min_damage,
+                stats.max_damage,
+                stats.reach,
+                stats.defense,
+            ),
+        );
     }
-    pub fn get_input(&self, prompt: String) -> String {
+    pub fn get_input(&mut self, prompt: String) -> String {
+        if let Interface::Headless(queue) = &mut self.interface {
+            return queue
+                .pop_front()
+                .unwrap_or_else(|| panic!("Ran out of scripted text input for prompt: {prompt}"));
+        }
+        if let Some(text) = self.replay.as_mut().and_then(|replay| replay.next_text()) {
+            self.record(crate::replay::Event::Text(text.clone()));
+            return text;
+        }
+        self.end_replay();
         // First we move to the input row and show the prompt
-        print!("\x1b[{};0H{prompt}", self.board.get_viewport_size().y + 6);
+        print!("\x1b[{};0H{prompt}", self.board.get_viewport_size().y + 7);
         // Then we make the terminal go back to normal
         crate::input::normalize().unwrap();
         // Make sure everything sends
@@ -296,9 +711,138 @@ impl State {
         // Fuck windows
         buf.pop();
         abes_nice_things::windows!(buf.pop());
+        self.record(crate::replay::Event::Text(buf.clone()));
+        // The prompt and what they typed were written around the screen
+        self.screen.invalidate();
         // And return!
         buf
     }
+    /// Asks a yes or no question, anything other than yes is no
+    pub fn confirm(&mut self, prompt: String) -> bool {
+        matches!(
+            self.get_input(format!("{prompt} (y/n) "))
+                .to_lowercase()
+                .as_str(),
+            "y" | "yes"
+        )
+    }
+    /// Replays shouldn't touch the saves, they would overwrite the ones of whoever is watching
+    pub fn can_write_saves(&self) -> bool {
+        self.replay.is_none()
+    }
+    /// Saves to the slot and tells the player how it went
+    pub fn save_to_slot(&mut self, name: &str) {
+        if !self.can_write_saves() {
+            self.feedback = format!("Not saving to {name} while replaying");
+            return;
+        }
+        self.feedback = match crate::save::save_to_slot(self, name) {
+            Ok(()) => format!("Saved to {name}"),
+            Err(error) => format!("Failed to save to {name}: {error:#}"),
+        };
+    }
+    /// Loads the slot and tells the player how it went, if it fails then nothing changes
+    pub fn load_from_slot(&mut self, name: &str) {
+        self.feedback = match self.load(|| crate::save::load_from_slot(name)) {
+            Ok(()) => format!("Loaded {name}"),
+            Err(error) => format!("Failed to load {name}: {error:#}"),
+        };
+    }
+    /// Replaces everything which gets saved with a state from outside of the game, if it fails
+    /// then nothing changes. What was loaded gets recorded and while replaying the recorded state
+    /// is loaded instead, so replays don't depend on what is on disk
+    pub fn load(&mut self, load: impl FnOnce() -> Result<State>) -> Result<()> {
+        let loaded = match self.replay.as_mut().and_then(|replay| replay.next_loaded()) {
+            Some(Some(save)) => crate::save::read(&mut save.as_slice()),
+            Some(None) => Err(anyhow::anyhow!("It failed to load when it was recorded")),
+            None => {
+                self.end_replay();
+                load()
+            }
+        };
+        let loaded = match loaded {
+            Ok(loaded) => loaded,
+            Err(error) => {
+                self.record(crate::replay::Event::Loaded(None));
+                return Err(error);
+            }
+        };
+        self.replace_with_loaded(loaded);
+        if self.recorder.is_some() {
+            let mut save = Vec::new();
+            match crate::save::write(self, &mut save) {
+                Ok(()) => self.record(crate::replay::Event::Loaded(Some(save))),
+                Err(error) => {
+                    self.recorder = None;
+                    self.feedback = format!("Stopped recording: {error:#}");
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Shows what happened during the run and asks what to do next until the player picks
+    /// something. Reloading happens here, restarting and quitting are up to the caller
+    pub fn game_over(&mut self) -> GameOver {
+        loop {
+            self.render_game_over();
+            let answer =
+                self.get_input("Restart, load the last save or quit? (r/l/q) ".to_string());
+            match answer.to_lowercase().as_str() {
+                "r" | "restart" => return GameOver::Restart,
+                "l" | "load" => {
+                    // Which slot is the latest is only checked when it isn't being replayed
+                    self.feedback = match self.load(|| {
+                        let slot = crate::save::latest_slot().context("There are no saves")?;
+                        crate::save::load_from_slot(&slot)
+                    }) {
+                        Ok(()) => "Loaded the last save".to_string(),
+                        Err(error) => format!("Failed to load the last save: {error:#}"),
+                    };
+                    // Loading can fail, in which case they are still dead
+                    if !self.player.is_dead() {
+                        return GameOver::Reloaded;
+                    }
+                }
+                "q" | "quit" => return GameOver::Quit,
+                _ => self.feedback = "Type r, l or q".to_string(),
+            }
+        }
+    }
+    fn render_game_over(&mut self) {
+        if self.is_headless() {
+            return;
+        }
+        print!(
+            "\x1b[H\x1b[2J{}You died\x1b[0m\r\n\
+            \r\n\
+            Turns survived: {}\r\n\
+            Enemies killed: {}\r\n\
+            Damage taken: {}\r\n\
+            \r\n\
+            {}",
+            abes_nice_things::Style::new().red().bold(true),
+            self.total_turns,
+            self.stats.kills,
+            self.stats.damage_taken,
+            self.feedback,
+        );
+        std::io::stdout().flush().unwrap();
+        self.screen.invalidate();
+    }
+    /// Replaces everything which gets saved while keeping what doesn't, this isn't recorded so
+    /// anything loaded during play should go through [State::load]
+    pub fn replace_with_loaded(&mut self, mut loaded: State) {
+        loaded.screen_size = self.screen_size;
+        loaded.fit_viewport();
+        loaded.interface = std::mem::replace(&mut self.interface, Interface::Terminal);
+        loaded.recorder = self.recorder.take();
+        loaded.replay = self.replay.take();
+        loaded.autosave_interval = self.autosave_interval;
+        loaded.screen = std::mem::take(&mut self.screen);
+        loaded.screen.invalidate();
+        *self = loaded;
+        self.finish_load();
+    }
     pub fn get_enemy_char(&mut self, vtable_id: crate::enemy::VTableID) -> char {
         if self
             .player
@@ -316,26 +860,109 @@ impl State {
             self.enemy_visuals[index].unwrap()
         }
     }
-    pub fn force_render_feedback(&self) {
+    pub fn force_render_feedback(&mut self) {
+        if self.is_headless() {
+            return;
+        }
         print!(
             "\x1b[{};0H{}",
             self.board.get_viewport_size().y + 2,
             self.feedback
         );
-        std::io::stdout().flush().unwrap()
+        std::io::stdout().flush().unwrap();
+        self.screen.invalidate();
+    }
+    /// Does everything a load affects outside of the state, this must only be done once the save
+    /// is known to be valid so that a failed load leaves the running game alone
+    pub fn finish_load(&mut self) {
+        if let Some(rng) = self.loaded_rng.take() {
+            crate::random::set_state(rng);
+        }
+        self.finish_load_effects();
     }
     /// Run all on_starts for all active effects which must be reran on load
     ///
     /// This MUST be run on load and no other times
     pub fn finish_load_effects(&mut self) {
-        for effect in crate::effect::EffectTracker::iter_effect_ids() {
-            if self.player.effect_tracker.has(effect) && effect.needs_on_start_rerun_on_load() {
-                effect.force_run_on_start(self, Entity::Player)
+        let entities = std::iter::once(Entity::Player).chain(
+            (0..self.board.enemies.len())
+                .map(|index| Entity::Enemy(crate::board::EnemyID(index)))
+                .collect::<Vec<_>>(),
+        );
+        for entity in entities {
+            for effect in crate::effect::EffectTracker::iter_effect_ids() {
+                if crate::effect::EffectTracker::of(self, entity)
+                    .is_some_and(|tracker| tracker.has(effect))
+                    && effect.needs_on_start_rerun_on_load()
+                {
+                    effect.force_run_on_start(self, entity)
+                }
             }
         }
     }
 }
 
+/// Reads the characters enemy types are drawn as, types added since the save was made are left to
+/// be picked when they are first drawn
+fn read_enemy_visuals(
+    binary: &mut dyn std::io::prelude::Read,
+) -> Result<[Option<char>; crate::enemy::VTABLES.len()]> {
+    // Before version 11 the count wasn't written, it was however many enemy types there were.
+    // Version 4 added the boss which was the last of them
+    let count = match crate::save::loading_version() {
+        11.. => usize::from_binary(binary)?,
+        4.. => 5,
+        _ => 2,
+    };
+    if count > crate::enemy::VTABLES.len() {
+        anyhow::bail!(
+            "Save has {count} enemy types but only {} exist",
+            crate::enemy::VTABLES.len()
+        );
+    }
+    let mut enemy_visuals = [None; crate::enemy::VTABLES.len()];
+    for enemy_visual in enemy_visuals.iter_mut().take(count) {
+        *enemy_visual = <Option<char>>::from_binary(binary)?;
+    }
+    Ok(enemy_visuals)
+}
+
+/// Where the state gets drawn to and where text prompts are answered from
+pub enum Interface {
+    Terminal,
+    /// Nothing gets drawn and text prompts are answered in order from the queue
+    Headless(VecDeque<String>),
+}
+
+/// What the player picked on the game over screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GameOver {
+    /// Start again on a new board
+    Restart,
+    /// The last save was loaded, so play can continue
+    Reloaded,
+    Quit,
+}
+/// Counts of what happened during the run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RunStats {
+    pub kills: usize,
+    pub damage_taken: usize,
+}
+impl ToBinary for RunStats {
+    fn to_binary(&self, binary: &mut dyn Write) -> Result<()> {
+        self.kills.to_binary(binary)?;
+        self.damage_taken.to_binary(binary)
+    }
+}
+impl FromBinary for RunStats {
+    fn from_binary(binary: &mut dyn std::io::prelude::Read) -> Result<Self> {
+        Ok(RunStats {
+            kills: usize::from_binary(binary)?,
+            damage_taken: usize::from_binary(binary)?,
+        })
+    }
+}
 /// Anything on the board, specifically the player an enemy or a tile
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum MapObject {
@@ -399,3 +1026,203 @@ impl FromBinary for Entity {
         })
     }
 }
+#[cfg(test)]
+impl State {
+    /// An empty small board with the player in the top left, shared by the tests of every module
+    pub fn blank_headless() -> State {
+        State::new_headless(
+            Board::new(crate::board::AxisLength::Small, HEADLESS_SCREEN_SIZE).unwrap(),
+            Player::new(Vector::new(1, 1)),
+            0,
+            0,
+        )
+    }
+    /// Writes the state how it was before saves had a header or a version, for testing that those
+    /// still load
+    pub fn write_version_0(&self, binary: &mut dyn Write) -> Result<()> {
+        self.board.write_version_0(binary)?;
+        self.player.write_version_0(binary)?;
+        self.total_turns.to_binary(binary)?;
+        self.context_menu_stack.len().to_binary(binary)?;
+        for (argument, index, menu) in self.context_menu_stack.iter() {
+            argument.as_ref().to_binary(binary)?;
+            index.to_binary(binary)?;
+            menu.to_binary(binary)?;
+        }
+        self.context_menu_inputs.to_binary(binary)?;
+        self.feedback.to_binary(binary)?;
+        // There were only two enemy types
+        for enemy_visual in self.enemy_visuals.iter().take(2) {
+            enemy_visual.as_ref().to_binary(binary)?;
+        }
+        self.next_enemy_visual.to_binary(binary)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn headless_skip_turns() {
+        let mut state = State::blank_headless();
+        state.simulate(std::iter::repeat_n(Input::SkipTurn, 1000));
+        assert_eq!(state.total_turns, 1000);
+        assert_eq!(state.board.get_local_turn(), 1000);
+    }
+    #[test]
+    fn headless_walk() {
+        let mut state = State::blank_headless();
+        state.simulate([Input::Walk(Direction::Right); 5]);
+        state.simulate([Input::Walk(Direction::Down); 3]);
+        assert_eq!(state.player.position, Vector::new(6, 4));
+        assert_eq!(state.total_turns, 8);
+    }
+    #[test]
+    fn headless_load() {
+        let mut state = State::blank_headless();
+        state.simulate([Input::Walk(Direction::Right); 2]);
+        let mut buf = VecDeque::new();
+        state.to_binary(&mut buf).unwrap();
+        let loaded = State::load_headless(&mut buf).unwrap();
+        assert!(loaded.is_headless());
+        assert_eq!(loaded.player.position, state.player.position);
+        assert_eq!(loaded.total_turns, 2);
+        assert_eq!(buf.len(), 0);
+    }
+    #[test]
+    fn enemy_visuals_from_old_saves() {
+        let mut buf = VecDeque::new();
+        Some(&'0').to_binary(&mut buf).unwrap();
+        None::<&char>.to_binary(&mut buf).unwrap();
+        let enemy_visuals =
+            crate::save::with_loading_version(3, || read_enemy_visuals(&mut buf)).unwrap();
+        assert_eq!(enemy_visuals[0], Some('0'));
+        assert!(
+            enemy_visuals[1..]
+                .iter()
+                .all(|enemy_visual| enemy_visual.is_none())
+        );
+        assert_eq!(buf.len(), 0);
+    }
+    #[test]
+    fn autosave_rotation() {
+        use crate::save::{AUTOSAVE_SLOTS, autosave_slot};
+        let directory = std::env::temp_dir().join(format!("saves-{}", std::process::id()));
+        crate::save::with_save_directory(&directory, || {
+            let mut state = State::blank_headless();
+            state.autosave_interval = Some(2);
+            // Autosaves 1 to 4, enough to come back around to the first slot
+            let autosaves = AUTOSAVE_SLOTS + 1;
+            state.simulate(std::iter::repeat_n(Input::SkipTurn, 2 * autosaves));
+            let mut slots: Vec<String> = (0..AUTOSAVE_SLOTS).map(autosave_slot).collect();
+            slots.sort();
+            assert_eq!(crate::save::list_slots(), slots);
+            // Each slot has the newest autosave which went to it
+            for autosave in autosaves + 1 - AUTOSAVE_SLOTS..=autosaves {
+                let loaded = crate::save::load_from_slot(&autosave_slot(autosave)).unwrap();
+                assert_eq!(loaded.total_turns, autosave * 2);
+            }
+            // Saves go through a temporary file which is always renamed into place
+            for entry in std::fs::read_dir(&directory).unwrap() {
+                let name = entry.unwrap().file_name().into_string().unwrap();
+                assert!(!name.ends_with(".tmp"), "{name} was left behind");
+            }
+
+            // Quickloading goes back to the quicksave
+            state.simulate([Input::QuickSave]);
+            let position = state.player.position;
+            state.simulate([Input::Walk(Direction::Right); 2]);
+            state.simulate([Input::QuickLoad]);
+            assert_eq!(state.player.position, position);
+            assert_eq!(state.feedback, "Loaded quicksave");
+        });
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+    #[test]
+    fn replays_dont_save() {
+        let mut state = State::blank_headless();
+        let path = std::env::temp_dir().join("replays_dont_save");
+        let path = path.to_str().unwrap();
+        crate::replay::Recorder::new(path, &state).unwrap();
+        let (_, replay) = crate::replay::Replay::load(path, std::time::Duration::ZERO).unwrap();
+        std::fs::remove_file(path).unwrap();
+        state.replay = Some(replay);
+        assert!(!state.can_write_saves());
+        state.save_to_slot("replays_dont_save");
+        assert!(!crate::save::slot_exists("replays_dont_save"));
+    }
+    #[test]
+    fn headless_text_input() {
+        let mut state = State::blank_headless();
+        state.push_text_input("first".to_string());
+        state.push_text_input("second".to_string());
+        assert_eq!(state.get_input("prompt".to_string()), "first");
+        assert_eq!(state.get_input("prompt".to_string()), "second");
+    }
+    #[test]
+    fn player_death() {
+        use crate::enemy::{Enemy, VTableID};
+        let mut state = State::blank_headless();
+        // Deaths the player didn't cause aren't kills
+        let id = state
+            .board
+            .add_enemy(Enemy::new(VTableID::Dummy, Vector::new(3, 3)));
+        (state.board[id].as_ref().unwrap().get_vtable().damage)(&mut state, id, 10000);
+        assert!(state.board[id].is_none());
+        assert_eq!(state.stats.kills, 0);
+        let id = state
+            .board
+            .add_enemy(Enemy::new(VTableID::Basic, Vector::new(1, 0)));
+        while state.board[id].is_some() {
+            Player::attack(&mut state, id);
+        }
+        assert_eq!(state.stats.kills, 1);
+
+        // Only the health actually lost counts
+        Player::damage(&mut state, 30);
+        assert!(!state.player.is_dead());
+        Player::damage(&mut state, 100);
+        assert!(state.player.is_dead());
+        assert_eq!(state.stats.damage_taken, 50);
+
+        state.push_text_input("what".to_string());
+        state.push_text_input("q".to_string());
+        assert_eq!(state.game_over(), GameOver::Quit);
+        assert_eq!(state.feedback, "Type r, l or q");
+
+        let mut buf = VecDeque::new();
+        state.stats.to_binary(&mut buf).unwrap();
+        assert_eq!(RunStats::from_binary(&mut buf).unwrap(), state.stats);
+        assert_eq!(buf.len(), 0);
+    }
+    #[test]
+    fn game_over_answers() {
+        let directory =
+            std::env::temp_dir().join(format!("game-over-saves-{}", std::process::id()));
+        crate::save::with_save_directory(&directory, || {
+            let mut state = State::blank_headless();
+            Player::damage(&mut state, 10000);
+            state.push_text_input("r".to_string());
+            assert_eq!(state.game_over(), GameOver::Restart);
+
+            // Without a save loading fails and they are asked again
+            state.push_text_input("l".to_string());
+            state.push_text_input("q".to_string());
+            assert_eq!(state.game_over(), GameOver::Quit);
+            assert_eq!(
+                state.feedback,
+                "Failed to load the last save: There are no saves"
+            );
+
+            // With one they come back from it
+            let mut saved = State::blank_headless();
+            saved.total_turns = 5;
+            crate::save::save_to_slot(&saved, "before death").unwrap();
+            state.push_text_input("l".to_string());
+            assert_eq!(state.game_over(), GameOver::Reloaded);
+            assert_eq!(state.feedback, "Loaded the last save");
+            assert!(!state.player.is_dead());
+            assert_eq!(state.total_turns, 5);
+        });
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,342 @@
+// Everything which talks to the terminal directly instead of just writing escape codes
+//
+// There is no libc dependency so the few functions and structs needed are declared here. The
+// termios layout is only declared where it is known to be right: linux on the architectures using
+// the generic layout, and macos. Everywhere else raw mode goes through stty like it always did.
+// The window size ioctl is the same struct everywhere, only the request number changes, and
+// platforms without a known number ask tput instead. Just like the rest of the terminal handling
+// this is unix only.
+
+use crate::math::Vector;
+use anyhow::Result;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+/// Set by the SIGWINCH handler and cleared when taken
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+mod ffi {
+    use std::ffi::{c_int, c_ulong};
+    pub const STDIN: c_int = 0;
+    pub const STDOUT: c_int = 1;
+    pub const STDERR: c_int = 2;
+
+    // Linux on mips numbers its signals like System V instead of like everything else
+    #[cfg(all(
+        any(target_os = "linux", target_os = "android"),
+        any(
+            target_arch = "mips",
+            target_arch = "mips64",
+            target_arch = "mips32r6",
+            target_arch = "mips64r6"
+        )
+    ))]
+    pub const SIGWINCH: Option<c_int> = Some(20);
+    #[cfg(any(
+        all(
+            any(target_os = "linux", target_os = "android"),
+            not(any(
+                target_arch = "mips",
+                target_arch = "mips64",
+                target_arch = "mips32r6",
+                target_arch = "mips64r6"
+            ))
+        ),
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "dragonfly"
+    ))]
+    pub const SIGWINCH: Option<c_int> = Some(28);
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "dragonfly"
+    )))]
+    pub const SIGWINCH: Option<c_int> = None;
+
+    // Linux on powerpc, mips and sparc kept the BSD ioctl numbers, every other linux architecture
+    // uses the generic ones
+    #[cfg(all(
+        any(target_os = "linux", target_os = "android"),
+        not(any(
+            target_arch = "powerpc",
+            target_arch = "powerpc64",
+            target_arch = "mips",
+            target_arch = "mips64",
+            target_arch = "mips32r6",
+            target_arch = "mips64r6",
+            target_arch = "sparc",
+            target_arch = "sparc64"
+        ))
+    ))]
+    pub const TIOCGWINSZ: Option<c_ulong> = Some(0x5413);
+    #[cfg(any(
+        all(
+            any(target_os = "linux", target_os = "android"),
+            any(
+                target_arch = "powerpc",
+                target_arch = "powerpc64",
+                target_arch = "mips",
+                target_arch = "mips64",
+                target_arch = "mips32r6",
+                target_arch = "mips64r6",
+                target_arch = "sparc",
+                target_arch = "sparc64"
+            )
+        ),
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "dragonfly"
+    ))]
+    pub const TIOCGWINSZ: Option<c_ulong> = Some(0x4008_7468);
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "dragonfly"
+    )))]
+    pub const TIOCGWINSZ: Option<c_ulong> = None;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct WinSize {
+        pub ws_row: u16,
+        pub ws_col: u16,
+        pub ws_xpixel: u16,
+        pub ws_ypixel: u16,
+    }
+
+    unsafe extern "C" {
+        pub fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+        pub fn siginterrupt(signum: c_int, flag: c_int) -> c_int;
+        pub fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+}
+
+/// Raw mode through termios, only where the layout of the struct is known
+#[cfg(any(
+    all(
+        target_os = "linux",
+        any(
+            target_arch = "x86",
+            target_arch = "x86_64",
+            target_arch = "arm",
+            target_arch = "aarch64",
+            target_arch = "riscv32",
+            target_arch = "riscv64",
+            target_arch = "loongarch64",
+            target_arch = "s390x"
+        )
+    ),
+    target_os = "macos",
+    target_os = "ios"
+))]
+mod raw_mode {
+    use anyhow::{Result, bail};
+    use std::ffi::c_int;
+
+    /// What the terminal was like before we changed it, this is what gets restored
+    static ORIGINAL: std::sync::Mutex<Option<Termios>> = std::sync::Mutex::new(None);
+
+    const TCSANOW: c_int = 0;
+
+    #[cfg(target_os = "linux")]
+    mod platform {
+        pub type TcFlag = u32;
+        pub const NCCS: usize = 32;
+        pub const ECHO: TcFlag = 0o10;
+        pub const ICANON: TcFlag = 0o2;
+        #[repr(C)]
+        #[derive(Clone, Copy, Debug)]
+        pub struct Termios {
+            pub c_iflag: TcFlag,
+            pub c_oflag: TcFlag,
+            pub c_cflag: TcFlag,
+            pub c_lflag: TcFlag,
+            pub c_line: u8,
+            pub c_cc: [u8; NCCS],
+            pub c_ispeed: u32,
+            pub c_ospeed: u32,
+        }
+    }
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    mod platform {
+        use std::ffi::c_ulong;
+        pub type TcFlag = c_ulong;
+        pub const NCCS: usize = 20;
+        pub const ECHO: TcFlag = 0x8;
+        pub const ICANON: TcFlag = 0x100;
+        #[repr(C)]
+        #[derive(Clone, Copy, Debug)]
+        pub struct Termios {
+            pub c_iflag: TcFlag,
+            pub c_oflag: TcFlag,
+            pub c_cflag: TcFlag,
+            pub c_lflag: TcFlag,
+            pub c_cc: [u8; NCCS],
+            pub c_ispeed: c_ulong,
+            pub c_ospeed: c_ulong,
+        }
+    }
+    use platform::*;
+
+    unsafe extern "C" {
+        fn tcgetattr(fd: c_int, termios: *mut Termios) -> c_int;
+        fn tcsetattr(fd: c_int, optional_actions: c_int, termios: *const Termios) -> c_int;
+    }
+
+    /// Turns off echo and line buffering, remembering how the terminal was so that [restore] can
+    /// put it back exactly
+    pub fn enable_raw_mode() -> Result<()> {
+        let mut termios = std::mem::MaybeUninit::<Termios>::uninit();
+        if unsafe { tcgetattr(super::ffi::STDIN, termios.as_mut_ptr()) } != 0 {
+            bail!(
+                "Failed to get terminal attributes: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        // tcgetattr succeeded so it is filled in
+        let original = unsafe { termios.assume_init() };
+        let mut modified = original;
+        modified.c_lflag &= !(ECHO | ICANON);
+        if unsafe { tcsetattr(super::ffi::STDIN, TCSANOW, &modified) } != 0 {
+            bail!(
+                "Failed to set terminal attributes: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        // Only the first original matters, later ones are already modified
+        ORIGINAL
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get_or_insert(original);
+        Ok(())
+    }
+    /// Puts the terminal back to how it was before [enable_raw_mode]
+    pub fn restore() -> Result<()> {
+        // This runs while panicking so a poisoned lock can't stop it
+        let original = *ORIGINAL
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(original) = original else {
+            return Ok(());
+        };
+        if unsafe { tcsetattr(super::ffi::STDIN, TCSANOW, &original) } != 0 {
+            bail!(
+                "Failed to reset terminal attributes: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        Ok(())
+    }
+}
+/// Raw mode through stty, for everywhere the termios layout isn't declared
+#[cfg(not(any(
+    all(
+        target_os = "linux",
+        any(
+            target_arch = "x86",
+            target_arch = "x86_64",
+            target_arch = "arm",
+            target_arch = "aarch64",
+            target_arch = "riscv32",
+            target_arch = "riscv64",
+            target_arch = "loongarch64",
+            target_arch = "s390x"
+        )
+    ),
+    target_os = "macos",
+    target_os = "ios"
+)))]
+mod raw_mode {
+    use anyhow::{Result, bail};
+
+    /// Turns off echo and line buffering
+    pub fn enable_raw_mode() -> Result<()> {
+        stty(&["-echo", "-icanon"])
+    }
+    /// Turns echo and line buffering back on
+    pub fn restore() -> Result<()> {
+        stty(&["echo", "icanon"])
+    }
+    fn stty(args: &[&str]) -> Result<()> {
+        if !std::process::Command::new("stty")
+            .args(args)
+            .status()?
+            .success()
+        {
+            bail!("Failed to modify terminal, stty {} failed", args.join(" "));
+        }
+        Ok(())
+    }
+}
+pub use raw_mode::{enable_raw_mode, restore};
+
+extern "C" fn on_resize(_: std::ffi::c_int) {
+    // Only atomics are safe in a signal handler
+    RESIZED.store(true, Ordering::Relaxed);
+}
+
+/// Starts listening for the terminal being resized. Resizing interrupts blocking reads from stdin
+/// so that [crate::input::Input::get] can react immediately. Where the signal number isn't known
+/// resizes are only noticed when the size is next asked for.
+pub fn watch_resizes() {
+    if let Some(sigwinch) = ffi::SIGWINCH {
+        unsafe {
+            ffi::signal(sigwinch, on_resize);
+            ffi::siginterrupt(sigwinch, 1);
+        }
+    }
+}
+/// Returns if the terminal was resized since the last time this was called
+pub fn take_resized() -> bool {
+    RESIZED.swap(false, Ordering::Relaxed)
+}
+/// Like [take_resized] but it stays set
+pub fn was_resized() -> bool {
+    RESIZED.load(Ordering::Relaxed)
+}
+/// Gets the size of the terminal in width, height. It asks stdout, then stdin, then stderr so
+/// that it still works when some of them are redirected. If none of them are a terminal then it
+/// is None.
+pub fn get_size() -> Option<Vector<usize>> {
+    let Some(request) = ffi::TIOCGWINSZ else {
+        return get_size_from_tput();
+    };
+    for fd in [ffi::STDOUT, ffi::STDIN, ffi::STDERR] {
+        let mut size = ffi::WinSize::default();
+        if unsafe { ffi::ioctl(fd, request, &mut size as *mut ffi::WinSize) } == 0
+            && size.ws_col != 0
+            && size.ws_row != 0
+        {
+            return Some(Vector::new(size.ws_col as usize, size.ws_row as usize));
+        }
+    }
+    None
+}
+/// Asks tput for the size, it has to inherit stderr because that is what it asks
+fn get_size_from_tput() -> Option<Vector<usize>> {
+    let ask = |what: &str| -> Result<usize> {
+        let output = std::process::Command::new("tput")
+            .arg(what)
+            .stderr(std::process::Stdio::inherit())
+            .output()?;
+        Ok(String::from_utf8(output.stdout)?.trim().parse()?)
+    };
+    Some(Vector::new(ask("cols").ok()?, ask("lines").ok()?))
+}